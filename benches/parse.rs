@@ -0,0 +1,76 @@
+//! Measures parsing a worksheet with tens of thousands of data points and
+//! writing it as erg file, as well as reading the fixture workbook of the
+//! end-to-end tests with `parse_workout`. Run with `cargo bench`, criterion
+//! compares every run with the previous one, `--save-baseline <name>` keeps
+//! a baseline to compare later changes with (`--baseline <name>`).
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, 
+                Throughput};
+use office::DataType;
+use xlsx2erg::{
+    parse_range, parse_workout, write_erg, Excel, ParseOptions, Range, 
+    WriteOptions,
+};
+
+/// Number of data points of the benchmarked worksheet
+const POINTS: usize = 50_000;
+
+/// Workbook of the end-to-end tests
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), 
+                              "/tests/fixtures/workout.xlsx");
+
+/// Builds a worksheet of `points` data points, alternating between steady 
+/// intervals and ramps of a minute each.
+fn large_sheet(points: usize) -> Range {
+    let header = 3;
+    let mut range = Range::new((0, 0), (header as usize + points, 2));
+    let text = |value: &str| DataType::String(value.to_string());
+    range.set_value((0, 0), text("FTP"));
+    range.set_value((0, 1), DataType::Int(250));
+    range.set_value((1, 0), text("FILE NAME"));
+    range.set_value((1, 1), text("large.erg"));
+    range.set_value((2, 0), text("MINUTES"));
+    range.set_value((2, 1), text("INTENSITY"));
+    for i in 0..points / 2 {
+        let start = 0.5 + (i % 10) as f64 * 0.05;
+        let end = if i % 2 == 0 { start } else { start + 0.2 };
+        let row = header + 2 * i as u32;
+        range.set_value((row, 0), DataType::Float(i as f64));
+        range.set_value((row, 1), DataType::Float(start));
+        range.set_value((row + 1, 0), DataType::Float((i + 1) as f64));
+        range.set_value((row + 1, 1), DataType::Float(end));
+    }
+    range
+}
+
+fn parse(c: &mut Criterion) {
+    let range = large_sheet(POINTS);
+    let options = ParseOptions::default();
+    let workout = parse_range(&range, &options).unwrap();
+    let write = WriteOptions::default();
+
+    let mut group = c.benchmark_group("large_sheet");
+    group.throughput(Throughput::Elements(POINTS as u64));
+    group.bench_function("parse", |b| b.iter(|| {
+        parse_range(black_box(&range), &options).unwrap()
+    }));
+    group.bench_function("write_erg", |b| b.iter(|| {
+        let mut erg = Vec::with_capacity(POINTS * 16);
+        write_erg(black_box(&workout), &write, &mut erg).unwrap();
+        erg
+    }));
+    group.finish();
+}
+
+fn workbook(c: &mut Criterion) {
+    let options = ParseOptions::default();
+    // opening the workbook is part of every run, so this includes reading 
+    // and unzipping the file
+    c.bench_function("workbook/parse_workout", |b| b.iter(|| {
+        let mut workbook = Excel::open(black_box(FIXTURE)).unwrap();
+        parse_workout(&mut workbook, "endurance", &options).unwrap()
+    }));
+}
+
+criterion_group!(benches, parse, workbook);
+criterion_main!(benches);
@@ -0,0 +1,5195 @@
+//! Converts workouts created in xlsx workbooks to `erg` and `mrc` files for 
+//! bike computers.
+//!
+//! Every worksheet of the workbook describes one `Workout` which can be read 
+//! with `parse_workout` and written with `write_erg`, `write_mrc`, 
+//! `write_json`, `write_gc_json` or `write_zwo`.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, prelude::*};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use log::{debug, trace};
+use office::DataType;
+use serde::{Serialize, Serializer};
+
+pub use office::{Excel, Range};
+
+/// `WorkoutData` represents a single row in the xlsx worksheet.
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct WorkoutData {
+    /// Timestamp in minutes of the data point
+    pub time: f64,
+    /// Relative intensity at `time` in percent of FTP
+    pub intensity: f64,
+    /// Target cadence in rpm, if the worksheet has a cadence column
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cadence: Option<f64>,
+    /// Target heart rate in fractions of the maximum heart rate, if the 
+    /// worksheet is prescribed by heart rate. `intensity` is derived from it 
+    /// with a `HeartRateCurve`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heart_rate: Option<f64>,
+    /// Label of the interval starting at the data point, e.g. `Threshold 1`, 
+    /// if the worksheet has one in the column right of the data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// `Interval` represents an interval which is created in the 
+/// `erg` file
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct Interval {
+    /// Time in minutes the interval takes
+    pub duration: f64,
+    /// Relative intensity at the start of the interval
+    pub start_intensity: f64,
+    /// Relative intensity at the end of the interval
+    pub end_intensity: f64,
+    /// Average watts of the interval
+    #[serde(serialize_with = "serialize_rounded")]
+    pub watt: f64,
+    /// Watts at the start and the end of the interval, which differ for 
+    /// ramps, e.g. for formats with a range of power per step
+    #[serde(serialize_with = "serialize_rounded")]
+    pub start_watt: f64,
+    #[serde(serialize_with = "serialize_rounded")]
+    pub end_watt: f64,
+    /// Intensity factor, i.e. the root mean square of the relative intensity, 
+    /// which is higher than the average intensity for ramps
+    #[serde(serialize_with = "serialize_rounded")]
+    pub intensity_factor: f64,
+    /// Training Stress Score of the interval
+    #[serde(serialize_with = "serialize_rounded")]
+    pub tss: f64,
+    /// Target cadence in rpm of the start of the interval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cadence: Option<f64>,
+    /// Label of the interval, see `WorkoutData`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// `TSS` of one hour at FTP. 100 is the standard definition by Coggan, 
+/// other scoring models may scale it differently.
+pub const TSS_SCALE: f64 = 100.0;
+
+impl Interval {
+    /// Creates a new `Interval`, requires to consecutive `WorkoutData` points 
+    /// and the current `FTP` as parameters.
+    pub fn new(wd1: &WorkoutData, wd2: &WorkoutData, ftp: f64) -> Self {
+        Self::scaled(wd1, wd2, ftp, TSS_SCALE)
+    }
+
+    /// Creates a new `Interval` like `new`, but one hour at FTP scores 
+    /// `tss_scale` instead of `TSS_SCALE`.
+    pub fn scaled(wd1: &WorkoutData, wd2: &WorkoutData, ftp: f64, 
+                  tss_scale: f64) -> Self {
+        let duration = wd2.time - wd1.time;
+        let (start, end) = (wd1.intensity, wd2.intensity);
+        let watt = (start + end) / 2.0 * ftp;
+        // integral of the linear intensity squared over the interval divided 
+        // by its duration, squaring the average would underrate ramps
+        let intensity_factor = 
+            ((start * start + start * end + end * end) / 3.0).sqrt();
+        let tss = (duration/60.0) * intensity_factor.powf(2.0) * tss_scale;
+        Self {
+            duration,
+            start_intensity: wd1.intensity,
+            end_intensity: wd2.intensity,
+            watt,
+            start_watt: start * ftp,
+            end_watt: end * ftp,
+            intensity_factor,
+            tss,
+            cadence: wd1.cadence,
+            label: wd1.label.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:6.1} min | {:4} W | IF: {:.2} | TSS: {:5.1}", 
+               self.duration, self.watt as u64, self.intensity_factor, 
+               self.tss)?;
+        match &self.label {
+            Some(label) => write!(f, " | {}", label),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The `Workout` struct represents the complete workout and contains 
+/// the current `FTP`, `file_name`, the `description` of the workout, 
+/// `Vectors` of `WorkoutData` and `Interval`s, as well as the total `TSS`, 
+/// the normalized power and the intensity factor derived from it.
+#[derive(Default, Debug, Serialize)]
+pub struct Workout {
+    pub ftp: f64,
+    pub file_name: String,
+    pub description: String,
+    pub units: Units,
+    pub workout_data: Vec<WorkoutData>,
+    pub intervals: Vec<Interval>,
+    #[serde(serialize_with = "serialize_rounded")]
+    pub tss: f64,
+    /// `TSS` per hour of the workout, i.e. how taxing it is regardless of its 
+    /// duration
+    #[serde(serialize_with = "serialize_rounded")]
+    pub tss_per_hour: f64,
+    #[serde(serialize_with = "serialize_rounded")]
+    pub normalized_power: f64,
+    #[serde(serialize_with = "serialize_rounded")]
+    pub intensity_factor: f64,
+    /// Minutes spent in each of the zones Z1 to Z7, see `ZONE_LIMITS`
+    pub zone_minutes: [f64; 7],
+    /// Maximum heart rate in bpm the heart rates of the data points refer to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hr_max: Option<f64>,
+    /// Problems found while parsing that don't prevent the conversion
+    #[serde(skip)]
+    pub warnings: Vec<Warning>,
+    /// Replaces `TSS_SCALE` when calculating the `TSS`
+    #[serde(skip)]
+    pub tss_scale: Option<f64>,
+    /// Text of the comment rows between the data points, e.g. `START OF 
+    /// THRESHOLD SET`, with the index of the data point following them
+    #[serde(skip)]
+    pub markers: Vec<(usize, String)>,
+}
+
+/// Serializes calculated values with three decimal places, which is precise 
+/// enough and avoids long fractions in the `json` output.
+fn serialize_rounded<S: Serializer>(value: &f64, serializer: S) 
+    -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64((value * 1000.0).round() / 1000.0)
+}
+
+/// Like `serialize_rounded`, for values which may be missing.
+fn serialize_rounded_option<S: Serializer>(value: &Option<f64>, 
+                                           serializer: S) 
+    -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serialize_rounded(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Upper limits of the Coggan power zones Z1 (active recovery) to Z6 
+/// (anaerobic capacity) as fraction of FTP. Everything above the last limit 
+/// is Z7 (neuromuscular power).
+pub const ZONE_LIMITS: [f64; 6] = [0.55, 0.75, 0.90, 1.05, 1.20, 1.50];
+
+impl Workout {
+    /// Compact breakdown of the minutes spent in each zone
+    pub fn zone_breakdown(&self) -> String {
+        self.zone_minutes.iter().enumerate()
+            .map(|(zone, minutes)| format!("Z{}: {:.0}", zone + 1, minutes))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// Total duration of all intervals in minutes
+    pub fn duration(&self) -> f64 {
+        self.intervals.iter().map(|i| i.duration).sum()
+    }
+
+    /// Average duration of the intervals in minutes, 0 without intervals
+    pub fn mean_interval_duration(&self) -> f64 {
+        match self.intervals.len() {
+            0 => 0.0,
+            count => self.duration() / count as f64,
+        }
+    }
+
+    /// `TSS` divided by the duration in hours, 0 for empty workouts
+    fn density(&self) -> f64 {
+        match self.duration() {
+            minutes if minutes > 0.0 => self.tss / (minutes / 60.0),
+            _ => 0.0,
+        }
+    }
+
+    /// Fits the workout to `minutes`, e.g. for plans whose sessions all take 
+    /// an hour. A shorter workout gets a longer cooldown, see `pad`. A longer 
+    /// one is cut off at `minutes` with `trim`, otherwise it's left alone 
+    /// with a `Warning::OverTarget`. `TSS` and the other values are 
+    /// recalculated with the added or removed time.
+    pub fn fit_duration(&mut self, minutes: f64, trim: bool) {
+        let duration = self.duration();
+        if (duration - minutes).abs() < 1e-9 {
+            return;
+        }
+        if duration < minutes {
+            self.pad(minutes - duration);
+        } else if trim {
+            self.trim(minutes);
+        } else {
+            self.warnings.push(Warning::OverTarget { 
+                minutes: duration, 
+                target: minutes,
+            });
+            return;
+        }
+        self.recalculate();
+    }
+
+    /// Adds `minutes` at recovery intensity to the end of the workout. A 
+    /// steady cooldown in the recovery zone is extended, otherwise a steady 
+    /// interval at the final intensity (at most `COOLDOWN_INTENSITY`) is 
+    /// appended.
+    fn pad(&mut self, minutes: f64) {
+        let (start, end) = match self.workout_data.as_slice() {
+            [.., start, end] => (start.clone(), end.clone()),
+            _ => return,
+        };
+        if start.intensity == end.intensity 
+            && end.intensity <= COOLDOWN_INTENSITY {
+            if let Some(last) = self.workout_data.last_mut() {
+                last.time += minutes;
+            }
+            return;
+        }
+        let intensity = end.intensity.min(COOLDOWN_INTENSITY);
+        let recovery = WorkoutData { 
+            intensity, 
+            // the heart rate only matches the unchanged intensity
+            heart_rate: end.heart_rate.filter(|_| intensity == end.intensity),
+            label: None,
+            ..end 
+        };
+        self.workout_data.push(recovery.clone());
+        self.workout_data.push(WorkoutData { 
+            time: end.time + minutes, 
+            ..recovery 
+        });
+    }
+
+    /// Drops the data after the first `minutes` of the intervals. The 
+    /// interval crossing that point ends there, at the intensity it has 
+    /// reached by then.
+    fn trim(&mut self, minutes: f64) {
+        let mut left = minutes;
+        let mut trimmed = Vec::new();
+        for pair in self.workout_data.chunks_exact(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            let duration = end.time - start.time;
+            if left <= 0.0 {
+                break;
+            }
+            if duration <= left {
+                trimmed.extend_from_slice(pair);
+                left -= duration;
+                continue;
+            }
+            let share = left / duration;
+            let between = |from: f64, to: f64| from + (to - from) * share;
+            trimmed.push(start.clone());
+            trimmed.push(WorkoutData {
+                time: start.time + left,
+                intensity: between(start.intensity, end.intensity),
+                heart_rate: start.heart_rate.zip(end.heart_rate)
+                    .map(|(from, to)| between(from, to)),
+                ..end.clone()
+            });
+            break;
+        }
+        self.workout_data = trimmed;
+    }
+
+    /// Smooths the intensities of the data points with a moving average over 
+    /// `window` points, see `smooth`. Call `recalculate` to update `TSS` and 
+    /// the other values.
+    pub fn smooth(&mut self, window: usize) {
+        let intensities: Vec<_> = self.workout_data.iter()
+            .map(|data| data.intensity)
+            .collect();
+        for (data, intensity) in self.workout_data.iter_mut()
+            .zip(smooth(&intensities, window)) {
+            data.intensity = intensity;
+        }
+    }
+
+    /// Calculates the intervals, `TSS`, normalized power, intensity factor and 
+    /// the time in zones from the pairs of data points.
+    pub fn recalculate(&mut self) {
+        let tss_scale = self.tss_scale.unwrap_or(TSS_SCALE);
+        self.intervals = self.workout_data.chunks_exact(2)
+            .map(|pair| Interval::scaled(&pair[0], &pair[1], self.ftp, 
+                                         tss_scale))
+            .collect();
+        self.tss = self.intervals.iter().map(|i| i.tss).sum();
+        self.tss_per_hour = self.density();
+        self.normalized_power = normalized_power(&self.workout_data, self.ftp);
+        self.intensity_factor = self.normalized_power / self.ftp;
+        self.zone_minutes = zone_minutes(&self.workout_data);
+    }
+
+    /// Limits the data points and intervals to `max_watts`. `TSS`, 
+    /// normalized power and time in zones still reflect the original 
+    /// intensities, call `recalculate` to update them as well. Returns the 
+    /// number of clamped data points.
+    pub fn clamp_watts(&mut self, max_watts: f64) -> usize {
+        let max_intensity = max_watts / self.ftp;
+        self.limit_intensities(|intensity| intensity.min(max_intensity))
+    }
+
+    /// Raises the data points and intervals below `min_watts` to it, e.g. 
+    /// for trainers which can't hold a lower resistance. Like with 
+    /// `clamp_watts`, call `recalculate` to update `TSS` and the other 
+    /// values. Returns the number of raised data points.
+    pub fn floor_watts(&mut self, min_watts: f64) -> usize {
+        let min_intensity = min_watts / self.ftp;
+        self.limit_intensities(|intensity| intensity.max(min_intensity))
+    }
+
+    /// Changes the FTP to `ftp`, keeping the intensities, so that the watts 
+    /// follow the new FTP. The `TSS` and the other values are recalculated 
+    /// from the pairs of data points, so call it before `interpolate_ramps` 
+    /// or `resample`.
+    pub fn set_ftp(&mut self, ftp: f64) {
+        self.ftp = ftp;
+        self.recalculate();
+    }
+
+    /// Replaces the intensities of the data points and intervals by 
+    /// `limit(intensity)`, keeping the `TSS` of the intervals. Returns the 
+    /// number of changed data points.
+    fn limit_intensities(&mut self, limit: impl Fn(f64) -> f64) -> usize {
+        let mut changed = 0;
+        for data in &mut self.workout_data {
+            let intensity = limit(data.intensity);
+            if intensity != data.intensity {
+                data.intensity = intensity;
+                changed += 1;
+            }
+        }
+        for interval in &mut self.intervals {
+            let start = WorkoutData { 
+                time: 0.0, 
+                intensity: limit(interval.start_intensity),
+                cadence: interval.cadence,
+                heart_rate: None,
+                label: interval.label.take(),
+            };
+            let end = WorkoutData { 
+                time: interval.duration, 
+                intensity: limit(interval.end_intensity),
+                cadence: None,
+                heart_rate: None,
+                label: None,
+            };
+            let tss = interval.tss;
+            *interval = Interval { 
+                tss, 
+                ..Interval::new(&start, &end, self.ftp) 
+            };
+        }
+        changed
+    }
+
+    /// Adds a data point every `resolution` minutes to ramps, i.e. between 
+    /// consecutive points with different intensities, for devices which 
+    /// don't ramp smoothly themselves. The intervals stay the same.
+    pub fn interpolate_ramps(&mut self, resolution: f64) {
+        self.workout_data = interpolate(&self.workout_data, resolution);
+    }
+
+    /// Resamples the intervals at every multiple of `resolution` minutes, 
+    /// steady ones included, for software which expects evenly spaced data 
+    /// points. The intervals stay the same, while `TSS`, normalized power and 
+    /// the time in zones are calculated from the sampled points.
+    pub fn resample(&mut self, resolution: f64) {
+        self.workout_data = resample(&self.workout_data, resolution);
+        let tss_scale = self.tss_scale.unwrap_or(TSS_SCALE);
+        self.tss = self.workout_data.windows(2)
+            .map(|pair| Interval::scaled(&pair[0], &pair[1], self.ftp, 
+                                         tss_scale).tss)
+            .sum();
+        self.tss_per_hour = self.density();
+        self.normalized_power = normalized_power(&self.workout_data, self.ftp);
+        self.intensity_factor = self.normalized_power / self.ftp;
+        self.zone_minutes = zone_minutes(&self.workout_data);
+    }
+}
+
+/// Returns the data points with additional points every `resolution` minutes 
+/// between consecutive points whose intensity differs. Steady blocks are 
+/// left untouched.
+fn interpolate(data: &[WorkoutData], resolution: f64) -> Vec<WorkoutData> {
+    let mut points = Vec::with_capacity(data.len());
+    for pair in data.windows(2) {
+        let (start, end) = (&pair[0], &pair[1]);
+        points.push(start.clone());
+        let duration = end.time - start.time;
+        if start.intensity == end.intensity || duration <= 0.0 {
+            continue;
+        }
+        let slope = (end.intensity - start.intensity) / duration;
+        let mut step = 1.0;
+        while step * resolution < duration - 1e-9 {
+            let offset = step * resolution;
+            points.push(WorkoutData {
+                time: start.time + offset,
+                intensity: start.intensity + slope * offset,
+                cadence: start.cadence,
+                // the intensity is linear in the heart rate
+                heart_rate: start.heart_rate.zip(end.heart_rate)
+                    .map(|(from, to)| from + (to - from) * offset / duration),
+                label: None,
+            });
+            step += 1.0;
+        }
+    }
+    if let Some(last) = data.last() {
+        points.push(last.clone());
+    }
+    points
+}
+
+/// Returns the start and end points of the intervals with additional points 
+/// at every multiple of `resolution` minutes within them.
+fn resample(data: &[WorkoutData], resolution: f64) -> Vec<WorkoutData> {
+    let mut points = Vec::with_capacity(data.len());
+    for pair in data.chunks_exact(2) {
+        let (start, end) = (&pair[0], &pair[1]);
+        points.push(start.clone());
+        let duration = end.time - start.time;
+        // the first multiple after the start, which may be one itself
+        let mut n = ((start.time + 1e-9) / resolution).floor() + 1.0;
+        while n * resolution < end.time - 1e-9 {
+            let share = (n * resolution - start.time) / duration;
+            let between = |from: f64, to: f64| from + (to - from) * share;
+            points.push(WorkoutData {
+                time: n * resolution,
+                intensity: between(start.intensity, end.intensity),
+                cadence: start.cadence,
+                heart_rate: start.heart_rate.zip(end.heart_rate)
+                    .map(|(from, to)| between(from, to)),
+                label: None,
+            });
+            n += 1.0;
+        }
+        points.push(end.clone());
+    }
+    points
+}
+
+/// Samples the intensity curve every `step` minutes. Between consecutive 
+/// points the intensity changes linearly, at a step (two points with the same 
+/// time) the later point wins.
+fn sample(data: &[WorkoutData], step: f64) -> Vec<f64> {
+    let mut samples = Vec::new();
+    let (first, last) = match (data.first(), data.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return samples,
+    };
+
+    let mut segment = 0;
+    let mut n = 0.0;
+    loop {
+        let time = first.time + n * step;
+        if time >= last.time {
+            break;
+        }
+        while segment + 2 < data.len() && data[segment + 1].time <= time {
+            segment += 1;
+        }
+        let (start, end) = (&data[segment], &data[segment + 1]);
+        let duration = end.time - start.time;
+        samples.push(if duration > 0.0 {
+            start.intensity + (end.intensity - start.intensity) 
+                * (time - start.time) / duration
+        } else {
+            end.intensity
+        });
+        n += 1.0;
+    }
+    samples
+}
+
+/// Calculates the normalized power of the data points: the power is sampled 
+/// every second, smoothed with a 30 second rolling average, raised to the 
+/// fourth power, averaged and finally the fourth root is taken.
+pub fn normalized_power(data: &[WorkoutData], ftp: f64) -> f64 {
+    let watts: Vec<f64> = sample(data, 1.0 / 60.0).iter()
+        .map(|intensity| intensity * ftp)
+        .collect();
+    if watts.is_empty() {
+        return 0.0;
+    }
+
+    let window = watts.len().min(30);
+    let mut rolling_sum: f64 = watts[..window].iter().sum();
+    let mut sum = (rolling_sum / window as f64).powi(4);
+    for i in window..watts.len() {
+        rolling_sum += watts[i] - watts[i - window];
+        sum += (rolling_sum / window as f64).powi(4);
+    }
+    let count = (watts.len() - window + 1) as f64;
+    (sum / count).powf(0.25)
+}
+
+/// Calculates the minutes spent in each power zone by sampling the intensity 
+/// curve every second.
+pub fn zone_minutes(data: &[WorkoutData]) -> [f64; 7] {
+    let mut minutes = [0.0; 7];
+    for intensity in sample(data, 1.0 / 60.0) {
+        let zone = ZONE_LIMITS.iter()
+            .position(|limit| intensity <= *limit)
+            .unwrap_or(ZONE_LIMITS.len());
+        minutes[zone] += 1.0 / 60.0;
+    }
+    minutes
+}
+
+/// Blocks of the `power_profile` from an eighth to a full line
+const PROFILE_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Renders the intensity over time as a bar chart of at most `width` columns 
+/// and `height` lines with Unicode blocks, e.g. to preview a workout in the 
+/// terminal. Every column shows the average intensity of its share of the 
+/// workout, sampled every second, and the highest column reaches the top.
+pub fn power_profile(workout: &Workout, width: usize, height: usize) 
+    -> Vec<String> {
+    let samples = sample(&workout.workout_data, 1.0 / 60.0);
+    let width = width.min(samples.len());
+    let columns: Vec<f64> = (0..width)
+        .map(|i| {
+            let bin = &samples[i * samples.len() / width
+                               ..(i + 1) * samples.len() / width];
+            bin.iter().sum::<f64>() / bin.len() as f64
+        })
+        .collect();
+    let top = columns.iter().cloned().fold(0.0, f64::max);
+    if top <= 0.0 {
+        return Vec::new();
+    }
+    (0..height).rev()
+        .map(|line| {
+            let chart: String = columns.iter()
+                .map(|intensity| {
+                    // eighths of a line above the bottom of this one
+                    let eighths = ((intensity / top * height as f64 
+                                    - line as f64) * 8.0).round();
+                    match eighths as usize {
+                        _ if eighths <= 0.0 => ' ',
+                        eighths => PROFILE_BLOCKS[eighths.min(8) - 1],
+                    }
+                })
+                .collect();
+            chart.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// Lists the intervals of the workout for debugging, one line per interval 
+/// with its start and end in minutes since the start of the workout (the 
+/// cumulative times of the `erg` format), its duration and its `TSS` as well 
+/// as the `TSS` of all intervals up to it. The first line is the header.
+pub fn interval_dump(workout: &Workout) -> Vec<String> {
+    let mut lines = vec![format!("{:>3} {:>9} {:>9} {:>9} {:>7} {:>7}", 
+                                 "#", "START", "END", "DURATION", "TSS", 
+                                 "TOTAL")];
+    let mut start = workout.workout_data.first().map_or(0.0, |d| d.time);
+    let mut total = 0.0;
+    for (i, interval) in workout.intervals.iter().enumerate() {
+        let end = start + interval.duration;
+        total += interval.tss;
+        lines.push(format!("{:3} {:9.3} {:9.3} {:9.3} {:7.2} {:7.2}", 
+                           i + 1, start, end, interval.duration, 
+                           interval.tss, total));
+        start = end;
+    }
+    lines
+}
+
+/// Splits the workout at its markers between two intervals into segments, 
+/// e.g. the named parts of a long endurance ride. The time of every segment 
+/// starts at zero and the file name gets the number of the segment appended, 
+/// see `numbered_file_name`. The description is followed by the text of the 
+/// marker starting the segment. Markers within an interval are ignored, a 
+/// workout without markers between its intervals is a single segment.
+pub fn split_workout(workout: &Workout) -> Vec<Workout> {
+    let count = workout.workout_data.len();
+    let mut starts: Vec<(usize, Option<&str>)> = vec![(0, None)];
+    for (index, text) in &workout.markers {
+        if !index.is_multiple_of(2) || *index >= count {
+            continue;
+        }
+        match starts.last_mut() {
+            // the last of several markers in a row names the segment
+            Some(last) if last.0 == *index => last.1 = Some(text),
+            _ => starts.push((*index, Some(text))),
+        }
+    }
+    let ends = starts.iter().skip(1).map(|(index, _)| *index).chain([count]);
+    starts.iter().zip(ends).enumerate()
+        .map(|(n, (&(start, marker), end))| {
+            let data = &workout.workout_data[start..end];
+            let offset = data.first().map_or(0.0, |d| d.time);
+            let description = match marker {
+                Some(marker) if !workout.description.is_empty() => 
+                    format!("{} - {}", workout.description, marker),
+                Some(marker) => marker.to_string(),
+                None => workout.description.clone(),
+            };
+            let mut segment = Workout {
+                ftp: workout.ftp,
+                file_name: numbered_file_name(&workout.file_name, n + 1),
+                description,
+                units: workout.units,
+                workout_data: data.iter()
+                    .map(|d| WorkoutData { time: d.time - offset, ..d.clone() })
+                    .collect(),
+                hr_max: workout.hr_max,
+                tss_scale: workout.tss_scale,
+                ..Default::default()
+            };
+            segment.recalculate();
+            segment
+        })
+        .collect()
+}
+
+/// Chains the `workouts` back to back into a single workout named 
+/// `file_name`: the time of every workout is shifted to start where the 
+/// previous one ended. All workouts need the same FTP (within 
+/// `FTP_TOLERANCE`), the `TSS` is the sum of their `TSS`. The first interval 
+/// of every workout is labeled with its description (or file name), unless 
+/// it has a label already.
+pub fn merge_workouts(workouts: &[Workout], file_name: &str) 
+    -> Result<Workout, MergeError> {
+    let first = workouts.first().ok_or(MergeError::NoWorkouts)?;
+    let mut merged = Workout {
+        ftp: first.ftp,
+        file_name: file_name.to_string(),
+        description: workouts.iter()
+            .map(|workout| workout.description.as_str())
+            .collect::<Vec<_>>()
+            .join(" + "),
+        units: first.units,
+        tss_scale: first.tss_scale,
+        ..Default::default()
+    };
+    let mismatches: Vec<(String, f64)> = workouts.iter()
+        .filter(|workout| (workout.ftp - merged.ftp).abs() > FTP_TOLERANCE)
+        .map(|workout| (workout.file_name.clone(), workout.ftp))
+        .collect();
+    if !mismatches.is_empty() {
+        return Err(MergeError::FtpMismatch { 
+            expected: merged.ftp, 
+            mismatches,
+        });
+    }
+    let mut end = 0.0;
+    for workout in workouts {
+        let start = workout.workout_data.first().map_or(0.0, |d| d.time);
+        let offset = merged.workout_data.len();
+        merged.workout_data.extend(workout.workout_data.iter()
+            .map(|d| WorkoutData { 
+                time: d.time - start + end, 
+                ..d.clone() 
+            }));
+        let first_interval = merged.intervals.len();
+        merged.intervals.extend(workout.intervals.iter().cloned());
+        // marks the boundary between the workouts in the written file
+        let label = if workout.description.is_empty() {
+            &workout.file_name
+        } else {
+            &workout.description
+        };
+        if let Some(data) = merged.workout_data.get_mut(offset) {
+            data.label.get_or_insert_with(|| label.clone());
+        }
+        if let Some(interval) = merged.intervals.get_mut(first_interval) {
+            interval.label.get_or_insert_with(|| label.clone());
+        }
+        end = merged.workout_data.last().map_or(end, |d| d.time);
+    }
+    merged.tss = workouts.iter().map(|workout| workout.tss).sum();
+    merged.tss_per_hour = merged.density();
+    merged.normalized_power = normalized_power(&merged.workout_data, 
+                                               merged.ftp);
+    merged.intensity_factor = merged.normalized_power / merged.ftp;
+    merged.zone_minutes = zone_minutes(&merged.workout_data);
+    Ok(merged)
+}
+
+impl fmt::Display for Workout {
+    /// Custom formatting so that it a quick summary of the workout can be 
+    /// printed to console after it is converted. The alternate form (`{:#}`) 
+    /// adds the number of intervals and their average duration.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cells = summary_cells(self, SummaryFormat::Plain, f.alternate(), 
+                                  0);
+        write!(f, "{}", SummaryFormat::Plain.join(&cells))
+    }
+}
+
+/// Layout of the summary lines printed for every workout
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SummaryFormat {
+    /// Aligned columns separated by `|`, each value with its label
+    #[default]
+    Plain,
+    /// Tab-separated values below a header, e.g. to paste them into a sheet
+    Tsv,
+    /// Markdown table
+    Markdown,
+}
+
+impl SummaryFormat {
+    /// Joins the cells of a summary line.
+    pub fn join<S: AsRef<str>>(self, cells: &[S]) -> String {
+        let cells: Vec<&str> = cells.iter().map(AsRef::as_ref).collect();
+        match self {
+            SummaryFormat::Plain => cells.join(" | "),
+            SummaryFormat::Tsv => cells.join("\t"),
+            SummaryFormat::Markdown => format!("| {} |", cells.join(" | ")),
+        }
+    }
+
+    /// Returns the header naming the columns `labels`, which plain summaries 
+    /// don't have since their values are labeled.
+    pub fn header(self, labels: &[&str]) -> Option<String> {
+        match self {
+            SummaryFormat::Plain => None,
+            SummaryFormat::Tsv => Some(self.join(labels)),
+            SummaryFormat::Markdown => {
+                let separator = vec!["---"; labels.len()];
+                Some(format!("{}\n{}", self.join(labels), 
+                             self.join(&separator)))
+            },
+        }
+    }
+}
+
+impl FromStr for SummaryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(SummaryFormat::Plain),
+            "tsv" => Ok(SummaryFormat::Tsv),
+            "markdown" => Ok(SummaryFormat::Markdown),
+            _ => Err(format!("unknown summary format '{}'", s)),
+        }
+    }
+}
+
+/// Labels of the columns of `summary_cells`
+pub fn summary_labels(detailed: bool) -> Vec<&'static str> {
+    let mut labels = vec!["FILE NAME", "TSS", "TSS/H", "NP", "IF"];
+    if detailed {
+        labels.extend(["INTERVALS", "AVERAGE MINUTES"]);
+    }
+    labels.push("DESCRIPTION");
+    labels
+}
+
+/// Returns the cells of the summary line of the workout: file name, `TSS`, 
+/// `TSS` per hour, normalized power, intensity factor and description. 
+/// `detailed` adds the number of intervals and their average duration. The 
+/// `TSS` is rounded to `tss_decimals` decimal places, the intensity factor to 
+/// two.
+pub fn summary_cells(workout: &Workout, format: SummaryFormat, 
+                     detailed: bool, tss_decimals: usize) -> Vec<String> {
+    let tss = format!("{:.*}", tss_decimals, workout.tss);
+    let tss_per_hour = format!("{:.*}", tss_decimals, workout.tss_per_hour);
+    let np = workout.normalized_power as u64;
+    let intervals = workout.intervals.len();
+    let mean = workout.mean_interval_duration();
+    let mut cells = match format {
+        SummaryFormat::Plain => vec![
+            format!("{:24}", workout.file_name),
+            format!("TSS: {:>5}", tss),
+            format!("TSS/h: {:>3}", tss_per_hour),
+            format!("NP: {:4}", np),
+            format!("IF: {:.2}", workout.intensity_factor),
+        ],
+        SummaryFormat::Tsv | SummaryFormat::Markdown => vec![
+            workout.file_name.clone(),
+            tss,
+            tss_per_hour,
+            np.to_string(),
+            format!("{:.2}", workout.intensity_factor),
+        ],
+    };
+    if detailed {
+        match format {
+            SummaryFormat::Plain => cells.push(
+                format!("{:3} intervals of {:5.1} min", intervals, mean)),
+            SummaryFormat::Tsv | SummaryFormat::Markdown => {
+                cells.push(intervals.to_string());
+                cells.push(format!("{:.1}", mean));
+            },
+        }
+    }
+    cells.push(match format {
+        SummaryFormat::Markdown => workout.description.replace('|', "\\|"),
+        _ => workout.description.clone(),
+    });
+    cells
+}
+
+/// Returns the line summing up the `TSS`, number and duration in `minutes` 
+/// of all converted workouts. The `TSS` is rounded to `tss_decimals` like in 
+/// the summaries.
+pub fn total_summary(tss: f64, count: usize, minutes: f64, 
+                     format: SummaryFormat, tss_decimals: usize) -> String {
+    let (tss, hours) = (format!("{:.*}", tss_decimals, tss), minutes / 60.0);
+    match format {
+        SummaryFormat::Plain => 
+            format!("TOTAL WEEK TSS: {} | {} workout(s) | {:.1} h", 
+                    tss, count, hours),
+        SummaryFormat::Tsv => 
+            format!("TOTAL WEEK TSS\t{}\t{}\t{:.1}", tss, count, hours),
+        // the empty line ends the table
+        SummaryFormat::Markdown => 
+            format!("\n**TOTAL WEEK TSS: {}** ({} workout(s), {:.1} h)", 
+                    tss, count, hours),
+    }
+}
+
+/// `TSS` of a worksheet in the previous and the revised version of a 
+/// workbook, see `compare_tss`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TssChange {
+    pub sheet: String,
+    /// `TSS` in the previous version, `None` if the worksheet is new
+    pub old: Option<f64>,
+    /// `TSS` in the revised version, `None` if the worksheet was removed
+    pub new: Option<f64>,
+}
+
+impl TssChange {
+    /// Returns whether the `TSS` rounded to `tss_decimals` changed or the 
+    /// worksheet is only in one of the versions.
+    pub fn changed(&self, tss_decimals: usize) -> bool {
+        match (self.old, self.new) {
+            (Some(old), Some(new)) => format!("{:.*}", tss_decimals, old) 
+                != format!("{:.*}", tss_decimals, new),
+            _ => true,
+        }
+    }
+}
+
+/// Matches the worksheets of two versions of a workbook, given by name and 
+/// `TSS`, by their names. The worksheets are returned in the order of the 
+/// `new` version, followed by the removed ones.
+pub fn compare_tss(old: &[(String, f64)], new: &[(String, f64)]) 
+    -> Vec<TssChange> {
+    let tss = |sheets: &[(String, f64)], sheet: &str| sheets.iter()
+        .find(|(name, _)| name == sheet)
+        .map(|(_, tss)| *tss);
+    let mut changes: Vec<_> = new.iter()
+        .map(|(sheet, new)| TssChange { 
+            sheet: sheet.clone(), 
+            old: tss(old, sheet), 
+            new: Some(*new),
+        })
+        .collect();
+    changes.extend(old.iter()
+        .filter(|(sheet, _)| tss(new, sheet).is_none())
+        .map(|(sheet, old)| TssChange { 
+            sheet: sheet.clone(), 
+            old: Some(*old), 
+            new: None,
+        }));
+    changes
+}
+
+/// Renders the `changes` between the workbooks `old_name` and `new_name` as 
+/// a table of the old and new `TSS` and the difference, rounded to 
+/// `tss_decimals`. Worksheets in only one of the workbooks are flagged.
+pub fn comparison_table(changes: &[TssChange], old_name: &str, 
+                        new_name: &str, tss_decimals: usize) -> Vec<String> {
+    let tss = |tss: Option<f64>| tss
+        .map_or("-".to_string(), |tss| format!("{:.*}", tss_decimals, tss));
+    let mut lines = vec![format!("{:<24} {:>8} {:>8} {:>8}", 
+                                 "WORKSHEET", "OLD TSS", "NEW TSS", "DELTA")];
+    lines.extend(changes.iter().map(|change| {
+        let (delta, flag) = match (change.old, change.new) {
+            (Some(_), Some(_)) if !change.changed(tss_decimals) => 
+                (format!("{:.*}", tss_decimals, 0.0), String::new()),
+            // the difference of the rounded values adds up
+            (Some(old), Some(new)) => {
+                let scale = 10f64.powi(tss_decimals as i32);
+                let round = |tss: f64| (tss * scale).round() / scale;
+                (format!("{:+.*}", tss_decimals, round(new) - round(old)), 
+                 String::new())
+            },
+            (Some(_), None) => 
+                ("-".to_string(), format!(" only in {}", old_name)),
+            _ => ("-".to_string(), format!(" only in {}", new_name)),
+        };
+        format!("{:<24} {:>8} {:>8} {:>8}{}", change.sheet, tss(change.old), 
+                tss(change.new), delta, flag)
+    }));
+    lines
+}
+
+/// Unit system declared in the `UNITS` line of the file header. Minutes and 
+/// watts are the same in both systems, so only the header line changes.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Units {
+    #[default]
+    English,
+    Metric,
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Units::English => write!(f, "ENGLISH"),
+            Units::Metric => write!(f, "METRIC"),
+        }
+    }
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if label_matches(s, "english") {
+            Ok(Units::English)
+        } else if label_matches(s, "metric") {
+            Ok(Units::Metric)
+        } else {
+            Err(format!("unknown units '{}'", s))
+        }
+    }
+}
+
+/// Unit of the intensity column declared next to an `INTENSITY UNIT` label, 
+/// which tells `0.85` from `85`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IntensityUnit {
+    /// Fractions of FTP, e.g. `0.85`
+    #[default]
+    Fraction,
+    /// Percent of FTP, e.g. `85`
+    Percent,
+}
+
+impl IntensityUnit {
+    /// Converts `intensity` in this unit to a fraction of FTP.
+    pub fn fraction(self, intensity: f64) -> f64 {
+        match self {
+            IntensityUnit::Fraction => intensity,
+            IntensityUnit::Percent => intensity / 100.0,
+        }
+    }
+}
+
+impl FromStr for IntensityUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if label_matches(s, "fraction") {
+            Ok(IntensityUnit::Fraction)
+        } else if label_matches(s, "percent") {
+            Ok(IntensityUnit::Percent)
+        } else {
+            Err(format!("unknown intensity unit '{}'", s))
+        }
+    }
+}
+
+/// `ParseError` describes why a worksheet couldn't be converted into a 
+/// `Workout`.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// The worksheet doesn't exist
+    SheetNotFound,
+    /// The worksheet exists, but its cells couldn't be read, e.g. because it's 
+    /// corrupt or protected
+    Unreadable { reason: String },
+    /// There's no `FTP` label or the cell next to it isn't a number
+    MissingFtp,
+    /// The FTP is zero or negative
+    InvalidFtp { ftp: f64 },
+    /// There's no `FILE NAME` label or the cell next to it isn't a string
+    MissingFileName,
+    /// The cell next to the `DESCRIPTION` label isn't a string
+    MissingDescription,
+    /// The data row with the given (1-based) row number isn't a pair of 
+    /// numbers. `cells` renders its content, see `row_cells`.
+    BadRow { row: usize, cells: String },
+    /// The data points can't be paired up into intervals
+    OddDataPoints { count: usize },
+    /// The time in the given (1-based) row is before the one of the previous 
+    /// data point
+    TimeNotIncreasing { row: usize },
+    /// The cell next to the `UNITS` label is neither `ENGLISH` nor `METRIC`
+    BadUnits { value: String },
+    /// The `REPEAT` (or `END REPEAT`) row with the given (1-based) row number 
+    /// doesn't enclose whole intervals, has no positive count or is nested
+    BadRepeat { row: usize },
+    /// The cell next to the `INTENSITY UNIT` label is neither `FRACTION` nor 
+    /// `PERCENT`
+    BadIntensityUnit { value: String },
+    /// The worksheet would be converted with the warning, which fails with 
+    /// `strict`
+    Strict { warning: Warning },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::SheetNotFound => 
+                write!(f, "worksheet not found"),
+            ParseError::Unreadable { reason } => 
+                write!(f, "couldn't read the worksheet: {}", reason),
+            ParseError::MissingFtp => 
+                write!(f, "FTP is missing or not a number"),
+            ParseError::InvalidFtp { ftp } => 
+                write!(f, "FTP has to be positive, but it is {}", ftp),
+            ParseError::MissingFileName => 
+                write!(f, "file name is missing or not a string"),
+            ParseError::MissingDescription => 
+                write!(f, "description is not a string"),
+            ParseError::BadRow { row, cells } => 
+                write!(f, "row {} is not a pair of numbers: {}", row, cells),
+            ParseError::TimeNotIncreasing { row } => 
+                write!(f, "time in row {} is before the previous one", row),
+            ParseError::OddDataPoints { count } => 
+                write!(f, "odd number of data points ({}), every interval \
+                       needs a start and an end point", count),
+            ParseError::BadUnits { value } => 
+                write!(f, "unknown units '{}'", value),
+            ParseError::BadRepeat { row } => 
+                write!(f, "repeat in row {} has to enclose whole intervals \
+                       between REPEAT <n> and END REPEAT", row),
+            ParseError::BadIntensityUnit { value } => 
+                write!(f, "unknown intensity unit '{}', expected fraction or \
+                       percent", value),
+            ParseError::Strict { warning } => 
+                write!(f, "strict: {}", warning),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// `MergeError` describes why workouts couldn't be merged with 
+/// `merge_workouts`.
+#[derive(Debug, PartialEq)]
+pub enum MergeError {
+    /// There's no workout to merge
+    NoWorkouts,
+    /// The FTP of these workouts (file name and FTP) differs from the one of 
+    /// the first workout
+    FtpMismatch { expected: f64, mismatches: Vec<(String, f64)> },
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::NoWorkouts => write!(f, "no workouts to merge"),
+            MergeError::FtpMismatch { expected, mismatches } => {
+                let mismatches: Vec<String> = mismatches.iter()
+                    .map(|(file_name, ftp)| format!("{} ({})", file_name, ftp))
+                    .collect();
+                write!(f, "FTP differs from the {} of the first workout: {}", 
+                       expected, mismatches.join(", "))
+            },
+        }
+    }
+}
+
+impl error::Error for MergeError {}
+
+/// FTPs which differ by at most this many watts are the same when merging, 
+/// e.g. after rounding in a spreadsheet
+pub const FTP_TOLERANCE: f64 = 1e-6;
+
+/// Intensities above this fraction of FTP are most likely percentages, e.g. 
+/// 85 instead of 0.85
+pub const MAX_INTENSITY: f64 = 3.0;
+
+/// A workout ending above this fraction of FTP (the upper limit of the 
+/// recovery zone) most likely lacks its cooldown
+pub const COOLDOWN_INTENSITY: f64 = ZONE_LIMITS[0];
+
+/// `Warning` describes a suspicious value in a worksheet, which is converted 
+/// anyway.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The intensity in the given (1-based) row is above `MAX_INTENSITY`
+    HighIntensity { row: usize, intensity: f64 },
+    /// The interval at the given time (in minutes) has no duration and was 
+    /// dropped
+    ZeroDuration { time: f64 },
+    /// The given (1-based) row between the data points is empty and was 
+    /// skipped
+    EmptyRow { row: usize },
+    /// The last data point is above `COOLDOWN_INTENSITY`
+    NoCooldown { intensity: f64 },
+    /// The intensity in the given (1-based) row is negative and was raised 
+    /// to 0
+    NegativeIntensity { row: usize, intensity: f64 },
+    /// The workout takes longer than the target duration, both in minutes
+    OverTarget { minutes: f64, target: f64 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::HighIntensity { row, intensity } => 
+                write!(f, "intensity {} in row {} is above {}, the intensity \
+                       has to be a fraction of FTP (e.g. 0.85 instead of 85)", 
+                       intensity, row, MAX_INTENSITY),
+            Warning::ZeroDuration { time } => 
+                write!(f, "dropped the interval at {} min, its start and end \
+                       have the same time", time),
+            Warning::EmptyRow { row } => 
+                write!(f, "skipped empty row {} between the data points", row),
+            Warning::NoCooldown { intensity } => 
+                write!(f, "the workout ends at an intensity of {}, is the \
+                       cooldown missing?", intensity),
+            Warning::NegativeIntensity { row, intensity } => 
+                write!(f, "intensity {} in row {} is negative, using 0 \
+                       instead", intensity, row),
+            Warning::OverTarget { minutes, target } => 
+                write!(f, "the workout takes {:.1} min, longer than the \
+                       target of {} min", minutes, target),
+        }
+    }
+}
+
+/// Returns the cell at (`row`, `col`) or `DataType::Empty` if the position 
+/// lies outside of the used range of the worksheet.
+fn cell(range: &Range, row: usize, col: usize) -> &DataType {
+    let (height, width) = range.get_size();
+    if row < height && col < width {
+        range.get_value(row, col)
+    } else {
+        &DataType::Empty
+    }
+}
+
+/// Puts `value` on a single line for the file header: line breaks, tabs and 
+/// runs of spaces become a single space, other control characters are 
+/// stripped. Any other text including `=` is kept as is, since the header 
+/// lines are split at their first `=`.
+fn header_value(value: &str) -> String {
+    value.split_whitespace()
+        .map(|word| word.chars().filter(|c| !c.is_control()).collect())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Writes the header of `erg` and `mrc` files with the given `KEY = value` 
+/// entries and the line naming the data columns, up to the start of the 
+/// data.
+fn write_course_header<W: Write>(out: &mut W, entries: &[(&str, String)], 
+                                 columns: &str, options: &WriteOptions) 
+    -> io::Result<()> {
+    let eol = options.line_ending.as_str();
+    write!(out, "[COURSE HEADER]{}", eol)?;
+    for (key, value) in entries {
+        write!(out, "{} = {}{}", key, value, eol)?;
+    }
+    write!(out, "{}{}[END COURSE HEADER]{}[COURSE DATA]{}", 
+           columns, eol, eol, eol)
+}
+
+/// Writes the parsed `Workout` in the `erg` format to `out`, e.g. a `File`,
+/// `io::stdout()` or a `Vec<u8>` buffer. With `annotate`, the header also 
+/// contains the total TSS and duration.
+pub fn write_erg<W: Write>(workout: &Workout, options: &WriteOptions, 
+                           out: &mut W) -> io::Result<()> {
+        check_rows(workout, options)?;
+        let eol = options.line_ending.as_str();
+        let mut header = vec![
+            ("VERSION", options.erg_version.to_string()),
+            ("UNITS", workout.units.to_string()),
+            ("DESCRIPTION", options.description(&workout.description)),
+            ("FILE NAME", header_value(&workout.file_name)),
+            ("FTP", workout.ftp.to_string()),
+        ];
+        if options.annotate {
+            header.push(("TOTAL TSS", format!("{:.1}", workout.tss)));
+            header.push(("TOTAL MINUTES", 
+                         format!("{:.2}", workout.duration())));
+        }
+        write_course_header(out, &header, "MINUTES WATTS", options)?;
+
+        for (data, intensity) in workout.workout_data.iter()
+            .zip(written_intensities(workout, options)) {
+            write_label(data, options, out)?;
+            write!(out, "{}\t{}{}", 
+                format_time(data.time, options), 
+                round_watts(intensity * workout.ftp, options.rounding), eol)?;
+        }
+
+        write!(out, "[END COURSE DATA]{}", eol)
+}
+
+/// Writes the heart rates of a workout prescribed by heart rate in the `erg` 
+/// format to `out`, with a `MINUTES HEARTRATE` data column in bpm, which some 
+/// trainers accept. Fails if the workout has no heart rates or no maximum 
+/// heart rate they refer to.
+pub fn write_hr_erg<W: Write>(workout: &Workout, options: &WriteOptions, 
+                              out: &mut W) -> io::Result<()> {
+        check_rows(workout, options)?;
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, 
+            format!("{} {}", workout.file_name, reason));
+        let hr_max = workout.hr_max
+            .ok_or_else(|| invalid("has no maximum heart rate"))?;
+        let heart_rates = workout.workout_data.iter()
+            .map(|data| data.heart_rate)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("isn't prescribed by heart rate"))?;
+        let eol = options.line_ending.as_str();
+        write_course_header(out, &[
+            ("VERSION", options.erg_version.to_string()),
+            ("UNITS", workout.units.to_string()),
+            ("DESCRIPTION", options.description(&workout.description)),
+            ("FILE NAME", header_value(&workout.file_name)),
+        ], "MINUTES HEARTRATE", options)?;
+
+        for (data, heart_rate) in workout.workout_data.iter().zip(heart_rates) {
+            write!(out, "{}\t{}{}", format_time(data.time, options), 
+                   round_watts(heart_rate * hr_max, options.rounding), eol)?;
+        }
+
+        write!(out, "[END COURSE DATA]{}", eol)
+}
+
+/// Writes the heart rates of the workout to an `erg` file in `outdir` using 
+/// `write_hr_erg`. Returns the path of the written file.
+pub fn write_hr_erg_file(workout: &Workout, outdir: &Path, 
+                         options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "erg");
+        // check the heart rates before creating the file
+        write_hr_erg(workout, options, &mut io::sink())?;
+        let mut out = BufWriter::new(create_output(outdir, &path, options)?);
+        write_hr_erg(workout, options, &mut out)?;
+        out.flush()?;
+        Ok(path)
+}
+
+/// Writes the label of the interval starting at `data` as a comment line 
+/// preceding its rows in `erg` and `mrc` files, if it has one.
+fn write_label<W: Write>(data: &WorkoutData, options: &WriteOptions, 
+                         out: &mut W) -> io::Result<()> {
+    match &data.label {
+        Some(label) => write!(out, "; {}{}", header_value(label), 
+                              options.line_ending.as_str()),
+        None => Ok(()),
+    }
+}
+
+/// Returns the intensities of the data points as written, i.e. smoothed with 
+/// `options.smooth`.
+fn written_intensities(workout: &Workout, options: &WriteOptions) -> Vec<f64> {
+    let intensities: Vec<_> = workout.workout_data.iter()
+        .map(|data| data.intensity)
+        .collect();
+    match options.smooth {
+        Some(window) => smooth(&intensities, window),
+        None => intensities,
+    }
+}
+
+/// Returns the moving average of `values` over `window` values centered on 
+/// each one, e.g. to soften single-row spikes some trainers handle badly. 
+/// Towards the ends the window shrinks symmetrically, so the first and last 
+/// values stay the same and the workout still starts and ends at its 
+/// warmup and cooldown intensity.
+pub fn smooth(values: &[f64], window: usize) -> Vec<f64> {
+    let half = window.saturating_sub(1) / 2;
+    (0..values.len())
+        .map(|i| {
+            let reach = half.min(i).min(values.len() - 1 - i);
+            let neighbours = &values[i - reach..=i + reach];
+            neighbours.iter().sum::<f64>() / neighbours.len() as f64
+        })
+        .collect()
+}
+
+/// Fails if the workout has more data points than `options.max_rows`, e.g. 
+/// because of a far too fine ramp resolution.
+fn check_rows(workout: &Workout, options: &WriteOptions) -> io::Result<()> {
+    let rows = workout.workout_data.len();
+    if rows > options.max_rows {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, 
+            format!("{} has {} data points, more than the limit of {}", 
+                    workout.file_name, rows, options.max_rows)));
+    }
+    Ok(())
+}
+
+/// Writes the parsed `Workout` to an `erg` file in `outdir` using 
+/// `write_erg`. `outdir` is created if it doesn't exist yet. Returns the path 
+/// of the written file.
+pub fn write_erg_file(workout: &Workout, outdir: &Path, 
+                      options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "erg");
+        // written to memory first, so a failure leaves no empty file behind
+        let mut erg = Vec::new();
+        write_erg(workout, options, &mut erg)?;
+        create_output(outdir, &path, options)?.write_all(&erg)?;
+        Ok(path)
+}
+
+/// Notation of the time column in `erg` and `mrc` files
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TimeFormat {
+    /// Decimal minutes, e.g. `5.50`
+    #[default]
+    Minutes,
+    /// Minutes and seconds, e.g. `05:30`. Not every tool supports this.
+    MinutesSeconds,
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minutes" => Ok(TimeFormat::Minutes),
+            "mm:ss" => Ok(TimeFormat::MinutesSeconds),
+            _ => Err(format!("unknown time format '{}'", s)),
+        }
+    }
+}
+
+/// Line terminator of `erg` and `mrc` files
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    /// `\r\n`, which some Windows-only trainer software expects
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::CrLf),
+            _ => Err(format!("unknown line ending '{}'", s)),
+        }
+    }
+}
+
+/// How watts are rounded to whole numbers in the output files
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Rounding {
+    #[default]
+    Nearest,
+    /// Towards zero, which underrates the workout a little
+    Truncate,
+    Ceil,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Rounding::Nearest),
+            "truncate" => Ok(Rounding::Truncate),
+            "ceil" => Ok(Rounding::Ceil),
+            _ => Err(format!("unknown rounding '{}'", s)),
+        }
+    }
+}
+
+/// Rounds `watts` to a whole number, negative watts become 0.
+pub fn round_watts(watts: f64, rounding: Rounding) -> u64 {
+    let watts = watts.max(0.0);
+    match rounding {
+        Rounding::Nearest => watts.round() as u64,
+        Rounding::Truncate => watts as u64,
+        Rounding::Ceil => watts.ceil() as u64,
+    }
+}
+
+/// Options for writing the output files
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Fail instead of overwriting existing files
+    pub no_clobber: bool,
+    /// Add the total TSS and duration to the `erg` header
+    pub annotate: bool,
+    /// Decimal places of the time column
+    pub time_precision: usize,
+    pub time_format: TimeFormat,
+    pub rounding: Rounding,
+    pub line_ending: LineEnding,
+    /// `VERSION` line of the `erg` and `mrc` header, one of `ERG_VERSIONS`
+    pub erg_version: u32,
+    /// Maximum number of data points of `erg` and `mrc` files
+    pub max_rows: usize,
+    /// Put in front of the description in the `erg` and `mrc` header
+    pub desc_prefix: String,
+    /// Appended to the description in the `erg` and `mrc` header
+    pub desc_suffix: String,
+    /// Window of the moving average over the data column of `erg` and `mrc` 
+    /// files, see `smooth`
+    pub smooth: Option<usize>,
+    /// Target absolute watts in `fit` files instead of percent of FTP
+    pub fit_watts: bool,
+}
+
+/// Default limit of the data points of `erg` and `mrc` files, far more than 
+/// any real workout has
+pub const DEFAULT_MAX_ROWS: usize = 1_000_000;
+
+/// Versions of the `erg`/`mrc` format which can be declared in the header
+pub const ERG_VERSIONS: [u32; 2] = [1, 2];
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            no_clobber: false,
+            annotate: false,
+            time_precision: 2,
+            time_format: TimeFormat::Minutes,
+            rounding: Rounding::Nearest,
+            line_ending: LineEnding::Lf,
+            erg_version: 2,
+            max_rows: DEFAULT_MAX_ROWS,
+            desc_prefix: String::new(),
+            desc_suffix: String::new(),
+            smooth: None,
+            fit_watts: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Returns the `description` between `desc_prefix` and `desc_suffix`, 
+    /// separated by spaces. Empty parts are left out, so a workout without 
+    /// description gets just the prefix and suffix.
+    pub fn description(&self, description: &str) -> String {
+        let parts = [&self.desc_prefix[..], description, &self.desc_suffix];
+        header_value(&parts.join(" "))
+    }
+}
+
+/// Formats a time in `minutes` for the time column of `erg` and `mrc` files.
+pub fn format_time(minutes: f64, options: &WriteOptions) -> String {
+    match options.time_format {
+        TimeFormat::Minutes => 
+            format!("{:.*}", options.time_precision, minutes),
+        TimeFormat::MinutesSeconds => {
+            let seconds = (minutes * 60.0).round() as u64;
+            format!("{:02}:{:02}", seconds / 60, seconds % 60)
+        },
+    }
+}
+
+/// Returns the path of the output file `file_name` in `outdir`. `extension` 
+/// is appended to a file name without one of the output extensions, which 
+/// are replaced instead, e.g. `workout.erg` becomes `workout.mrc`.
+pub fn output_path(outdir: &Path, file_name: &str, extension: &str) -> PathBuf {
+    let path = Path::new(file_name);
+    if !has_output_extension(path) {
+        return outdir.join(format!("{}.{}", file_name, extension));
+    }
+    match path.extension() {
+        // keep the spelling of the worksheet, e.g. `workout.ERG`
+        Some(current) if current.eq_ignore_ascii_case(extension) => 
+            outdir.join(path),
+        _ => outdir.join(path.with_extension(extension)),
+    }
+}
+
+/// Returns whether `path` ends with the extension of one of the `FORMATS`.
+fn has_output_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|current| FORMATS.iter()
+        .any(|format| current.eq_ignore_ascii_case(format.extension())))
+}
+
+/// Returns `file_name` with `-<n>` appended to its name before the output 
+/// extension, e.g. `workout-2.erg`, to tell apart workouts with the same file 
+/// name.
+pub fn numbered_file_name(file_name: &str, n: usize) -> String {
+    let path = Path::new(file_name);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) if has_output_extension(path) => {
+            let numbered = format!("{}-{}.{}", stem.to_string_lossy(), n, 
+                                   extension.to_string_lossy());
+            path.with_file_name(numbered).to_string_lossy().into_owned()
+        },
+        _ => format!("{}-{}", file_name, n),
+    }
+}
+
+/// Characters which aren't allowed in file names on common file systems
+const ILLEGAL_FILE_NAME_CHARS: &[char] = 
+    &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Fills in the placeholders of `template` to name the output file of the 
+/// workout: `{sheet}` (the worksheet), `{name}` (the file name of the 
+/// worksheet without extension), `{tss}`, `{minutes}` and `{date}`. 
+/// Characters which aren't allowed in file names are replaced with `_`.
+pub fn render_file_name(template: &str, workout: &Workout, sheet: &str, 
+                        date: &str) -> Result<String, String> {
+    let name = Path::new(&workout.file_name).file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or(format!("unclosed placeholder in '{}'", template))?;
+        let value = match &rest[start + 1..start + end] {
+            "sheet" => sheet.to_string(),
+            "name" => name.clone(),
+            "tss" => format!("{}", workout.tss.round() as u64),
+            "minutes" => format!("{}", workout.duration().round() as u64),
+            "date" => date.to_string(),
+            other => return Err(format!("unknown placeholder '{{{}}}'", 
+                                        other)),
+        };
+        rendered.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered.chars()
+       .map(|c| if ILLEGAL_FILE_NAME_CHARS.contains(&c) || c.is_control() { 
+           '_' 
+       } else { 
+           c 
+       })
+       .collect())
+}
+
+/// Creates the file at `path` and `outdir` if it doesn't exist yet. With 
+/// `no_clobber`, an existing file is an error.
+fn create_output(outdir: &Path, path: &Path, options: &WriteOptions) 
+    -> io::Result<File> {
+    fs::create_dir_all(outdir)?;
+    if !options.no_clobber {
+        return File::create(path);
+    }
+    OpenOptions::new().write(true).create_new(true).open(path)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => io::Error::new(e.kind(), 
+                format!("{} already exists", path.display())),
+            _ => e,
+        })
+}
+
+/// Writes the parsed `Workout` in the `mrc` format to `out`. In contrast to 
+/// `erg` files the data column holds the intensity in percent of FTP instead 
+/// of watts.
+pub fn write_mrc<W: Write>(workout: &Workout, options: &WriteOptions, 
+                           out: &mut W) -> io::Result<()> {
+        check_rows(workout, options)?;
+        let file_name = Path::new(&workout.file_name).with_extension("mrc");
+        let eol = options.line_ending.as_str();
+        write_course_header(out, &[
+            ("VERSION", options.erg_version.to_string()),
+            ("UNITS", workout.units.to_string()),
+            ("DESCRIPTION", options.description(&workout.description)),
+            ("FILE NAME", header_value(&file_name.to_string_lossy())),
+        ], "MINUTES PERCENT", options)?;
+
+        for (data, intensity) in workout.workout_data.iter()
+            .zip(written_intensities(workout, options)) {
+            write_label(data, options, out)?;
+            write!(out, "{}\t{:.1}{}",
+                format_time(data.time, options), intensity * 100.0, eol)?;
+        }
+
+        write!(out, "[END COURSE DATA]{}", eol)
+}
+
+/// Writes the parsed `Workout` to an `mrc` file in `outdir` using 
+/// `write_mrc`. `outdir` is created if it doesn't exist yet. Returns the path 
+/// of the written file.
+pub fn write_mrc_file(workout: &Workout, outdir: &Path, 
+                      options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "mrc");
+        // written to memory first like the `erg` files
+        let mut mrc = Vec::new();
+        write_mrc(workout, options, &mut mrc)?;
+        create_output(outdir, &path, options)?.write_all(&mrc)?;
+        Ok(path)
+}
+
+/// Writes the parsed `Workout` including its intervals as `json` to `out`. 
+/// The field names match the ones of the structs.
+pub fn write_json<W: Write>(workout: &Workout, out: &mut W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, workout)?;
+        writeln!(out)
+}
+
+/// Writes the parsed `Workout` to a `json` file in `outdir` using 
+/// `write_json`. `outdir` is created if it doesn't exist yet. Returns the path 
+/// of the written file.
+pub fn write_json_file(workout: &Workout, outdir: &Path, 
+                      options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "json");
+        let mut out = BufWriter::new(create_output(outdir, &path, options)?);
+        write_json(workout, &mut out)?;
+        out.flush()?;
+        Ok(path)
+}
+
+/// Escapes the characters with a special meaning in `xml`.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Writes the parsed `Workout` as Zwift workout (`zwo`) to `out`. Steady 
+/// intervals become a `SteadyState`, intervals whose start and end intensity 
+/// differ become a `Ramp`. Labels are shown as a `textevent` at the start of 
+/// their interval.
+pub fn write_zwo<W: Write>(workout: &Workout, out: &mut W) -> io::Result<()> {
+        let name = escape_xml(&workout.description);
+        writeln!(out, "<workout_file>")?;
+        writeln!(out, "    <name>{}</name>", name)?;
+        writeln!(out, "    <description>{}</description>", name)?;
+        writeln!(out, "    <sportType>bike</sportType>")?;
+        writeln!(out, "    <workout>")?;
+        for interval in &workout.intervals {
+            let duration = (interval.duration * 60.0).round() as u64;
+            let cadence = interval.cadence
+                .map(|cadence| format!(" Cadence=\"{}\"", cadence.round()))
+                .unwrap_or_default();
+            let element = if interval.start_intensity 
+                == interval.end_intensity {
+                write!(out, "        <SteadyState Duration=\"{}\" \
+                             Power=\"{}\"{}", 
+                       duration, interval.start_intensity, cadence)?;
+                "SteadyState"
+            } else {
+                write!(out, "        <Ramp Duration=\"{}\" PowerLow=\"{}\" \
+                             PowerHigh=\"{}\"{}", 
+                       duration, interval.start_intensity, 
+                       interval.end_intensity, cadence)?;
+                "Ramp"
+            };
+            match &interval.label {
+                Some(label) => {
+                    writeln!(out, ">")?;
+                    writeln!(out, "            <textevent timeoffset=\"0\" \
+                                  message=\"{}\"/>", escape_xml(label))?;
+                    writeln!(out, "        </{}>", element)?;
+                },
+                None => writeln!(out, "/>")?,
+            }
+        }
+        writeln!(out, "    </workout>")?;
+        writeln!(out, "</workout_file>")
+}
+
+/// Writes the parsed `Workout` to a `zwo` file in `outdir` using 
+/// `write_zwo`. `outdir` is created if it doesn't exist yet. Returns the path 
+/// of the written file.
+pub fn write_zwo_file(workout: &Workout, outdir: &Path, 
+                      options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "zwo");
+        let mut out = BufWriter::new(create_output(outdir, &path, options)?);
+        write_zwo(workout, &mut out)?;
+        out.flush()?;
+        Ok(path)
+}
+
+/// Base types of the fields of `fit` messages
+const FIT_ENUM: u8 = 0x00;
+const FIT_STRING: u8 = 0x07;
+const FIT_UINT16: u8 = 0x84;
+const FIT_UINT32: u8 = 0x86;
+
+/// Global numbers of the `fit` messages of a workout file
+const FIT_FILE_ID: u16 = 0;
+const FIT_WORKOUT: u16 = 26;
+const FIT_WORKOUT_STEP: u16 = 27;
+
+/// Longest step ramps are split into in `fit` files, in minutes
+pub const FIT_RAMP_STEP: f64 = 1.0;
+
+/// Calculates the CRC of `fit` files, see the FIT protocol description.
+fn fit_crc(bytes: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 
+        0xA001, 0x6C00, 0x7800, 0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+    bytes.iter().fold(0, |crc, &byte| {
+        let crc = (crc >> 4) ^ TABLE[(crc & 0xF) as usize] 
+            ^ TABLE[(byte & 0xF) as usize];
+        (crc >> 4) ^ TABLE[(crc & 0xF) as usize] ^ TABLE[(byte >> 4) as usize]
+    })
+}
+
+/// Records of a `fit` file, which consist of definition messages declaring 
+/// the fields of a local message type and data messages of such a type
+#[derive(Default)]
+struct FitRecords(Vec<u8>);
+
+impl FitRecords {
+    /// Defines the local message type `local` as the global message `global` 
+    /// with the given fields: number, size and base type.
+    fn define(&mut self, local: u8, global: u16, fields: &[(u8, u8, u8)]) {
+        // reserved byte and little endian architecture
+        self.0.extend_from_slice(&[0x40 | local, 0, 0]);
+        self.0.extend_from_slice(&global.to_le_bytes());
+        self.0.push(fields.len() as u8);
+        for &(number, size, base_type) in fields {
+            self.0.extend_from_slice(&[number, size, base_type]);
+        }
+    }
+
+    /// Appends a message of the local type `local` with the encoded values 
+    /// of its fields.
+    fn message(&mut self, local: u8, values: &[&[u8]]) {
+        self.0.push(local);
+        for value in values {
+            self.0.extend_from_slice(value);
+        }
+    }
+
+    /// Returns the complete file: header, records and CRC.
+    fn into_file(self) -> Vec<u8> {
+        // protocol version 2.0, profile version 21.32
+        let mut file = vec![14, 0x20];
+        file.extend_from_slice(&2132u16.to_le_bytes());
+        file.extend_from_slice(&(self.0.len() as u32).to_le_bytes());
+        file.extend_from_slice(b".FIT");
+        let header_crc = fit_crc(&file);
+        file.extend_from_slice(&header_crc.to_le_bytes());
+        file.extend_from_slice(&self.0);
+        let crc = fit_crc(&file);
+        file.extend_from_slice(&crc.to_le_bytes());
+        file
+    }
+}
+
+/// Returns the steps of a `fit` workout, i.e. their duration in minutes and 
+/// the lowest and highest watts. Steady intervals become one step with a 
+/// single target, ramps are approximated by steps of at most 
+/// `FIT_RAMP_STEP`, each ranging from the watts at its start to the ones at 
+/// its end.
+fn fit_steps(workout: &Workout) -> Vec<(f64, f64, f64)> {
+    let mut steps = Vec::new();
+    for interval in &workout.intervals {
+        let (start, end) = (interval.start_watt, interval.end_watt);
+        if start == end {
+            steps.push((interval.duration, start, end));
+            continue;
+        }
+        let count = (interval.duration / FIT_RAMP_STEP).ceil().max(1.0);
+        let watts = |i: usize| start + (end - start) * i as f64 / count;
+        for i in 0..count as usize {
+            let (first, last) = (watts(i), watts(i + 1));
+            steps.push((interval.duration / count, first.min(last), 
+                        first.max(last)));
+        }
+    }
+    steps
+}
+
+/// Writes the intervals of the parsed `Workout` as a FIT workout to `out`, 
+/// as read by Garmin and many other head units. Every step lasts a fixed 
+/// time and targets a range of power in percent of FTP or, with 
+/// `fit_watts`, absolute watts, see `fit_steps`.
+pub fn write_fit<W: Write>(workout: &Workout, options: &WriteOptions, 
+                           out: &mut W) -> io::Result<()> {
+        let steps = fit_steps(workout);
+        let count = u16::try_from(steps.len()).map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidData, 
+            format!("{} has too many steps for a fit file", 
+                    workout.file_name)))?;
+        let mut name = if workout.description.is_empty() {
+            Path::new(&workout.file_name).file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into())
+        } else {
+            header_value(&workout.description)
+        };
+        // strings are null-terminated and at most 255 bytes long
+        while name.len() > 254 {
+            name.pop();
+        }
+        let mut name = name.into_bytes();
+        name.push(0);
+
+        let mut fit = FitRecords::default();
+        // type workout, development manufacturer and product
+        fit.define(0, FIT_FILE_ID, &[(0, 1, FIT_ENUM), (1, 2, FIT_UINT16), 
+                                     (2, 2, FIT_UINT16)]);
+        fit.message(0, &[&[5], &255u16.to_le_bytes(), &0u16.to_le_bytes()]);
+        // sport cycling, number of steps and name
+        fit.define(1, FIT_WORKOUT, &[(4, 1, FIT_ENUM), (6, 2, FIT_UINT16), 
+                                     (8, name.len() as u8, FIT_STRING)]);
+        fit.message(1, &[&[2], &count.to_le_bytes(), &name]);
+        // message index, duration type time in ms, target type power with 
+        // a custom range and intensity active
+        fit.define(2, FIT_WORKOUT_STEP, &[
+            (254, 2, FIT_UINT16), (1, 1, FIT_ENUM), (2, 4, FIT_UINT32), 
+            (3, 1, FIT_ENUM), (4, 4, FIT_UINT32), (5, 4, FIT_UINT32), 
+            (6, 4, FIT_UINT32), (7, 1, FIT_ENUM),
+        ]);
+        // watts are offset by 1000 to tell them from percentages
+        let target = |watts: f64| if options.fit_watts {
+            round_watts(watts, options.rounding) as u32 + 1000
+        } else {
+            ((watts / workout.ftp * 100.0).round() as u32).min(1000)
+        };
+        for (i, (minutes, low, high)) in steps.into_iter().enumerate() {
+            let duration = (minutes * 60000.0).round() as u32;
+            fit.message(2, &[&(i as u16).to_le_bytes(), &[0], 
+                             &duration.to_le_bytes(), &[4], 
+                             &0u32.to_le_bytes(), &target(low).to_le_bytes(), 
+                             &target(high).to_le_bytes(), &[0]]);
+        }
+        out.write_all(&fit.into_file())
+}
+
+/// Writes the parsed `Workout` to a `fit` file in `outdir` using 
+/// `write_fit`. Returns the path of the written file.
+pub fn write_fit_file(workout: &Workout, outdir: &Path, 
+                      options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "fit");
+        let mut out = BufWriter::new(create_output(outdir, &path, options)?);
+        write_fit(workout, options, &mut out)?;
+        out.flush()?;
+        Ok(path)
+}
+
+/// A workout as imported by GoldenCheetah
+#[derive(Serialize)]
+struct GcWorkout<'a> {
+    name: &'a str,
+    workout: Vec<GcSegment>,
+}
+
+/// A segment of a `GcWorkout`, the duration in seconds and absolute watts
+#[derive(Serialize)]
+struct GcSegment {
+    duration: u64,
+    wattage: u64,
+}
+
+/// Writes the intervals of the parsed `Workout` as GoldenCheetah `json` to 
+/// `out`. The description becomes the name of the workout and every interval 
+/// a segment with its average watts.
+pub fn write_gc_json<W: Write>(workout: &Workout, options: &WriteOptions, 
+                               out: &mut W) -> io::Result<()> {
+        let gc_workout = GcWorkout {
+            name: &workout.description,
+            workout: workout.intervals.iter()
+                .map(|interval| GcSegment {
+                    duration: (interval.duration * 60.0).round() as u64,
+                    wattage: round_watts(interval.watt, options.rounding),
+                })
+                .collect(),
+        };
+        serde_json::to_writer_pretty(&mut *out, &gc_workout)?;
+        writeln!(out)
+}
+
+/// Writes the parsed `Workout` to a GoldenCheetah `json` file in `outdir` 
+/// using `write_gc_json`. `outdir` is created if it doesn't exist yet. 
+/// Returns the path of the written file.
+pub fn write_gc_json_file(workout: &Workout, outdir: &Path, 
+                          options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "json");
+        let mut out = BufWriter::new(create_output(outdir, &path, options)?);
+        write_gc_json(workout, options, &mut out)?;
+        out.flush()?;
+        Ok(path)
+}
+
+/// Machine-readable outcome of a run, e.g. for CI pipelines
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Whether the run succeeded, i.e. its exit code is zero
+    pub success: bool,
+    /// Why the run failed
+    pub error: Option<String>,
+    /// `TSS` of all converted workouts
+    #[serde(serialize_with = "serialize_rounded")]
+    pub total_tss: f64,
+    /// Where the merged workout was written to
+    pub merged: Option<String>,
+    pub sheets: Vec<SheetReport>,
+}
+
+/// Outcome of converting a single worksheet or `csv` file
+#[derive(Debug, Serialize)]
+pub struct SheetReport {
+    /// Workbook or `csv` file
+    pub file: String,
+    /// Worksheet, `None` for `csv` files and workbooks which couldn't be read
+    pub sheet: Option<String>,
+    pub converted: bool,
+    /// Why the worksheet couldn't be converted
+    pub error: Option<String>,
+    /// Where the workout was written to, `None` for dry runs and merges
+    pub output: Option<String>,
+    #[serde(serialize_with = "serialize_rounded_option")]
+    pub tss: Option<f64>,
+}
+
+impl SheetReport {
+    /// Returns the entry of a `workout` which was converted to `output`.
+    pub fn converted(file: &str, sheet: Option<&str>, workout: &Workout, 
+                     output: Option<&str>) -> Self {
+        SheetReport {
+            file: file.to_string(),
+            sheet: sheet.map(str::to_string),
+            converted: true,
+            error: None,
+            output: output.map(str::to_string),
+            tss: Some(workout.tss),
+        }
+    }
+
+    /// Returns the entry of a worksheet which couldn't be converted.
+    pub fn failed(file: &str, sheet: Option<&str>, error: &dyn fmt::Display) 
+        -> Self {
+        SheetReport {
+            file: file.to_string(),
+            sheet: sheet.map(str::to_string),
+            converted: false,
+            error: Some(error.to_string()),
+            output: None,
+            tss: None,
+        }
+    }
+}
+
+/// Writes the `report` as `json` to `out`.
+pub fn write_report<W: Write>(report: &Report, out: &mut W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, report)?;
+        writeln!(out)
+}
+
+/// Writes the `report` to the file at `path` using `write_report`. Its 
+/// directory is created if necessary and an existing report is replaced.
+pub fn write_report_file(report: &Report, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = BufWriter::new(File::create(path)?);
+        write_report(report, &mut out)?;
+        out.flush()
+}
+
+/// Writes a table with file name, duration, `TSS` and intensity factor of 
+/// every workout to `out`.
+pub fn write_overview<W: Write>(workouts: &[Workout], out: &mut W) 
+    -> io::Result<()> {
+        writeln!(out, "{:24} | {:>8} | {:>5} | {:>4}", 
+                 "FILE NAME", "MINUTES", "TSS", "IF")?;
+        for workout in workouts {
+            writeln!(out, "{:24} | {:8.1} | {:5.0} | {:4.2}", 
+                     workout.file_name, workout.duration(), 
+                     workout.tss, workout.intensity_factor)?;
+        }
+        Ok(())
+}
+
+/// Writes the overview of all `workouts` to `summary.txt` in `outdir` using 
+/// `write_overview`. Returns the path of the written file.
+pub fn write_overview_file(workouts: &[Workout], outdir: &Path, 
+                           options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = outdir.join("summary.txt");
+        let mut out = BufWriter::new(create_output(outdir, &path, options)?);
+        write_overview(workouts, &mut out)?;
+        out.flush()?;
+        Ok(path)
+}
+
+/// Writes a table of the intervals of the workout to `out`, one row with 
+/// start and end time, duration, average watts, intensity factor and `TSS` 
+/// per interval, e.g. for reviewing a workout with a coach.
+pub fn write_intervals<W: Write>(workout: &Workout, options: &WriteOptions, 
+                                 out: &mut W) -> io::Result<()> {
+        writeln!(out, "{:>3} | {:>7} | {:>7} | {:>8} | {:>5} | {:>4} | {:>5}", 
+                 "#", "START", "END", "MINUTES", "WATTS", "IF", "TSS")?;
+        let mut start = workout.workout_data.first().map_or(0.0, |d| d.time);
+        for (i, interval) in workout.intervals.iter().enumerate() {
+            let end = start + interval.duration;
+            writeln!(out, 
+                     "{:3} | {:>7} | {:>7} | {:8.2} | {:5} | {:4.2} | {:5.1}", 
+                     i + 1, format_time(start, options), 
+                     format_time(end, options), interval.duration, 
+                     round_watts(interval.watt, options.rounding), 
+                     interval.intensity_factor, interval.tss)?;
+            start = end;
+        }
+        Ok(())
+}
+
+/// Writes the intervals of the workout to an `intervals.txt` file in `outdir` 
+/// using `write_intervals`, e.g. `workout.intervals.txt` next to 
+/// `workout.erg`. Returns the path of the written file.
+pub fn write_intervals_file(workout: &Workout, outdir: &Path, 
+                            options: &WriteOptions) -> io::Result<PathBuf> {
+        let path = output_path(outdir, &workout.file_name, "intervals.txt");
+        let mut out = BufWriter::new(create_output(outdir, &path, options)?);
+        write_intervals(workout, options, &mut out)?;
+        out.flush()?;
+        Ok(path)
+}
+
+/// Output formats a `Workout` can be written as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Absolute watts
+    Erg,
+    /// Percent of FTP
+    Mrc,
+    /// The complete `Workout` as `json`
+    Json,
+    /// GoldenCheetah `json` workout
+    GcJson,
+    /// Zwift workout
+    Zwo,
+    /// `erg` file with heart rates in bpm instead of watts
+    HrErg,
+    /// Binary FIT workout
+    Fit,
+}
+
+/// All output formats
+pub const FORMATS: [Format; 7] = [Format::Erg, Format::Mrc, Format::Json, 
+                                  Format::GcJson, Format::Zwo, Format::HrErg, 
+                                  Format::Fit];
+
+impl Format {
+    /// Returns the extension of the files in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Erg | Format::HrErg => "erg",
+            Format::Mrc => "mrc",
+            Format::Json | Format::GcJson => "json",
+            Format::Zwo => "zwo",
+            Format::Fit => "fit",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "erg" => Ok(Format::Erg),
+            "mrc" => Ok(Format::Mrc),
+            "json" => Ok(Format::Json),
+            "gc-json" => Ok(Format::GcJson),
+            "zwo" => Ok(Format::Zwo),
+            "hr-erg" => Ok(Format::HrErg),
+            "fit" => Ok(Format::Fit),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/// Order the worksheets of a workbook are converted in
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SheetOrder {
+    /// By name, e.g. `Week10` before `Week2`
+    #[default]
+    Lexical,
+    /// By name with numbers compared by value, e.g. `Week2` before `Week10`
+    Natural,
+    /// As arranged in the workbook
+    Workbook,
+}
+
+impl SheetOrder {
+    /// Sorts `sheets` in this order.
+    pub fn sort(self, sheets: &mut [String]) {
+        match self {
+            SheetOrder::Lexical => sheets.sort(),
+            SheetOrder::Natural => sheets.sort_by(|a, b| natural_cmp(a, b)),
+            SheetOrder::Workbook => {},
+        }
+    }
+}
+
+impl FromStr for SheetOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lexical" => Ok(SheetOrder::Lexical),
+            "natural" => Ok(SheetOrder::Natural),
+            "none" => Ok(SheetOrder::Workbook),
+            _ => Err(format!("unknown sort order '{}'", s)),
+        }
+    }
+}
+
+/// Compares `a` and `b` like `str::cmp`, except that runs of digits are 
+/// compared by their value. Names which only differ in leading zeros are 
+/// ordered lexically.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a, b);
+    loop {
+        let (c, d) = match (x.chars().next(), y.chars().next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) => (c, d),
+        };
+        if c.is_ascii_digit() && d.is_ascii_digit() {
+            let m = x.find(|c: char| !c.is_ascii_digit()).unwrap_or(x.len());
+            let n = y.find(|c: char| !c.is_ascii_digit()).unwrap_or(y.len());
+            let i = x[..m].trim_start_matches('0');
+            let j = y[..n].trim_start_matches('0');
+            let order = i.len().cmp(&j.len()).then_with(|| i.cmp(j));
+            if order != Ordering::Equal {
+                return order;
+            }
+            x = &x[m..];
+            y = &y[n..];
+        } else if c != d {
+            return c.cmp(&d);
+        } else {
+            x = &x[c.len_utf8()..];
+            y = &y[d.len_utf8()..];
+        }
+    }
+}
+
+/// Returns the numeric value of a cell. Besides floats, this accepts integers 
+/// and strings containing a number, because whole numbers like the FTP may be 
+/// stored as either.
+pub fn coerce_number(value: &DataType) -> Option<f64> {
+    match value {
+        DataType::Float(value) => Some(*value),
+        DataType::Int(value) => Some(*value as f64),
+        DataType::String(value) => value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Columns (0-based) of the data points of worksheets, by default `A` and 
+/// `B`. Any other columns left of the data are ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataColumns {
+    pub time: usize,
+    pub intensity: usize,
+}
+
+impl Default for DataColumns {
+    fn default() -> Self {
+        DataColumns { time: 0, intensity: 1 }
+    }
+}
+
+impl DataColumns {
+    /// Returns the column right of the data, which holds the cadence or the 
+    /// label.
+    fn end(&self) -> usize {
+        self.time.max(self.intensity) + 1
+    }
+}
+
+/// Parses a worksheet column given by its letters (`A`, `C`, `AA`) or its 
+/// 0-based index (`0`, `2`, `26`).
+pub fn parse_column(column: &str) -> Result<usize, String> {
+    let invalid = || format!("invalid column '{}'", column);
+    if let Ok(index) = column.parse() {
+        return Ok(index);
+    }
+    if column.is_empty() || !column.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invalid());
+    }
+    column.to_ascii_uppercase().bytes()
+        .try_fold(0usize, |index, letter| index.checked_mul(26)
+                  .and_then(|index| index.checked_add(
+                      (letter - b'A') as usize + 1)))
+        .map(|index| index - 1)
+        .ok_or_else(invalid)
+}
+
+/// Row used as data start when a worksheet has neither a data header row nor 
+/// a row of numbers
+const DEFAULT_DATA_START: usize = 4;
+
+/// Cells next to the labels in the header of a worksheet and the row the 
+/// data points start in.
+#[derive(Default)]
+struct Header<'a> {
+    ftp: Option<&'a DataType>,
+    file_name: Option<&'a DataType>,
+    /// The cell next to the `DESCRIPTION` label followed by the cells below 
+    /// it which continue the description
+    description: Vec<&'a DataType>,
+    units: Option<&'a DataType>,
+    intensity_unit: Option<&'a DataType>,
+    data_start: usize,
+    /// Whether the data has a third column labeled `CADENCE`
+    cadence: bool,
+    /// Whether the second data column is labeled `WATTS`
+    watts: bool,
+    /// Whether the second data column is labeled `HR`
+    heart_rate: bool,
+    hr_max: Option<&'a DataType>,
+}
+
+/// Scans the header of the worksheet for the labels `FTP`, `FILE NAME`, 
+/// `DESCRIPTION`, `UNITS` and `INTENSITY UNIT` (case-insensitive) and picks 
+/// the cell right of each label. Long descriptions may continue in the cells 
+/// below, down to an empty cell or the next label. The header ends at the 
+/// row starting with `MINUTES` or `TIME` in the time column, which labels the 
+/// data columns, optionally followed by a `CADENCE` column. If the intensity 
+/// column is labeled `WATTS`, it contains absolute watts, if it's labeled 
+/// `HR`, the heart rate relative to the one next to `HR MAX`. Without such a 
+/// row, the data starts at the first row with numbers in both data columns. 
+/// `header_rows` overrides both and ends the header after that many rows.
+fn scan_header(range: &Range, header_rows: Option<usize>, 
+               columns: DataColumns) -> Header<'_> {
+    let mut header = Header { 
+        data_start: DEFAULT_DATA_START, 
+        ..Default::default() 
+    };
+    for (i, row) in range.rows().enumerate() {
+        if header_rows == Some(i) {
+            header.data_start = i;
+            break;
+        }
+        if is_data_header(row, columns) {
+            header.data_start = header_rows.unwrap_or(i + 1);
+            let labeled = |j: usize, expected: &str| matches!(row.get(j), 
+                Some(DataType::String(label)) 
+                    if label_matches(label, expected));
+            header.watts = labeled(columns.intensity, "watts");
+            header.heart_rate = labeled(columns.intensity, "hr");
+            header.cadence = labeled(columns.end(), "cadence");
+            break;
+        }
+        if header_rows.is_none() && is_data_row(row, columns) {
+            header.data_start = i;
+            break;
+        }
+        for (j, label) in row.iter().enumerate() {
+            let label = match label {
+                DataType::String(label) => fold_label(label),
+                _ => continue,
+            };
+            let value = Some(cell(range, i, j + 1));
+            match label.as_str() {
+                "ftp" => header.ftp = value,
+                "file name" => header.file_name = value,
+                "description" => 
+                    header.description = description(range, i, j, columns),
+                "units" => header.units = value,
+                "intensity unit" => header.intensity_unit = value,
+                "hr max" => header.hr_max = value,
+                _ => (),
+            }
+        }
+    }
+    header
+}
+
+/// Returns whether the label of a cell, a worksheet name or a declaration 
+/// like the `UNITS` is `expected`, ignoring the case and surrounding 
+/// whitespace, e.g. `" Overview "` for `OVERVIEW`.
+pub fn label_matches(label: &str, expected: &str) -> bool {
+    fold_label(label) == fold_label(expected)
+}
+
+/// Returns the label in lower case without surrounding whitespace, which is 
+/// how `label_matches` and `scan_header` compare labels.
+fn fold_label(label: &str) -> String {
+    label.trim().to_lowercase()
+}
+
+/// Returns whether the time column of the row holds `MINUTES` or `TIME`, 
+/// which labels the data columns.
+fn is_data_header(row: &[DataType], columns: DataColumns) -> bool {
+    matches!(row.get(columns.time), Some(DataType::String(label)) 
+        if label_matches(label, "minutes") || label_matches(label, "time"))
+}
+
+/// Returns whether both data columns of the row hold numbers, i.e. it's a 
+/// data point.
+fn is_data_row(row: &[DataType], columns: DataColumns) -> bool {
+    let number = |value: Option<&DataType>| 
+        matches!(value, Some(DataType::Float(_)) | Some(DataType::Int(_)));
+    number(row.get(columns.time)) && number(row.get(columns.intensity))
+}
+
+/// Returns the cell right of the `DESCRIPTION` label at (`row`, `col`) and 
+/// the strings below it, as long as there's no label next to them.
+fn description(range: &Range, row: usize, col: usize, columns: DataColumns) 
+    -> Vec<&DataType> {
+    let mut cells = vec![cell(range, row, col + 1)];
+    for (i, next) in range.rows().enumerate().skip(row + 1) {
+        match cell(range, i, col + 1) {
+            value @ DataType::String(_) 
+                if *cell(range, i, col) == DataType::Empty 
+                    && !is_data_header(next, columns) => 
+                cells.push(value),
+            _ => break,
+        }
+    }
+    cells
+}
+
+/// Unit of the time column of worksheets and `csv` files
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TimeUnit {
+    #[default]
+    Minutes,
+    Seconds,
+}
+
+impl TimeUnit {
+    /// Converts `time` in this unit to minutes.
+    pub fn minutes(self, time: f64) -> f64 {
+        match self {
+            TimeUnit::Minutes => time,
+            TimeUnit::Seconds => time / 60.0,
+        }
+    }
+}
+
+impl FromStr for TimeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minutes" => Ok(TimeUnit::Minutes),
+            "seconds" => Ok(TimeUnit::Seconds),
+            _ => Err(format!("unknown time unit '{}'", s)),
+        }
+    }
+}
+
+/// Options for parsing worksheets and `csv` files
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Replaces the FTP of the worksheet before the intervals are calculated
+    pub ftp: Option<f64>,
+    /// End the data points at the first empty row instead of skipping empty 
+    /// rows between them, and fail with `ParseError::Strict` instead of 
+    /// adding a `Warning`
+    pub strict: bool,
+    /// The intensity column contains absolute watts instead of fractions of 
+    /// FTP, like a worksheet labeling it `WATTS`
+    pub watts: bool,
+    /// Replaces `TSS_SCALE`, i.e. the `TSS` of one hour at FTP
+    pub tss_scale: Option<f64>,
+    /// Number of rows before the data points of worksheets, replaces the 
+    /// detection by the `MINUTES`/`TIME` row or the first row of numbers
+    pub header_rows: Option<usize>,
+    /// Unit of the time column, the times are converted to minutes
+    pub time_unit: TimeUnit,
+    /// Converts the heart rates of worksheets prescribed by heart rate to 
+    /// intensities
+    pub hr_curve: HeartRateCurve,
+    /// Replaces the maximum heart rate of the worksheets
+    pub hr_max: Option<f64>,
+    /// Columns of the time and intensity of worksheets
+    pub columns: DataColumns,
+}
+
+/// Linear relationship between heart rate and power, used to calculate the 
+/// intensities and `TSS` of workouts prescribed by heart rate. The heart 
+/// rates are fractions of the maximum heart rate. This is a rough 
+/// approximation, since the heart rate lags behind the power and drifts 
+/// upwards in long efforts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeartRateCurve {
+    /// Heart rate which corresponds to no power at all
+    pub rest: f64,
+    /// Heart rate which corresponds to FTP, i.e. at the lactate threshold
+    pub threshold: f64,
+}
+
+impl Default for HeartRateCurve {
+    /// The lactate threshold is usually at about 90% of the maximum heart 
+    /// rate, easy spinning at about 50%.
+    fn default() -> Self {
+        HeartRateCurve { rest: 0.5, threshold: 0.9 }
+    }
+}
+
+impl HeartRateCurve {
+    /// Returns the intensity in fractions of FTP at `heart_rate`.
+    pub fn intensity(self, heart_rate: f64) -> f64 {
+        (heart_rate - self.rest) / (self.threshold - self.rest)
+    }
+}
+
+impl FromStr for HeartRateCurve {
+    type Err = String;
+
+    /// Parses `<rest>,<threshold>`, e.g. `0.5,0.9`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid heart rate curve '{}', expected \
+                                 <rest>,<threshold> like 0.5,0.9", s);
+        let (rest, threshold) = s.split_once(',').ok_or_else(invalid)?;
+        match (rest.trim().parse(), threshold.trim().parse()) {
+            (Ok(rest), Ok(threshold)) if 0.0 <= rest && rest < threshold => 
+                Ok(HeartRateCurve { rest, threshold }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// A calendar date, e.g. of a worksheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+/// Days since 1970-01-01 of Excel's day 0, dates are stored as days since 
+/// then
+const EXCEL_EPOCH: i64 = -25569;
+
+impl Date {
+    /// Returns the date if it exists in the calendar.
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return None,
+        };
+        if (1..=days).contains(&day) {
+            Some(Date { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the date `days` after 1970-01-01, see 
+    /// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    pub fn from_days(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 
+                           + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era 
+            - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { 
+            month_index + 3 
+        } else { 
+            month_index - 9 
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year: year as i32, month: month as u32, day: day as u32 }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    /// Parses `YYYY-MM-DD`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match find_date(s) {
+            Some(date) if date.to_string() == s.trim() => Ok(date),
+            _ => Err(format!("invalid date '{}', expected YYYY-MM-DD", s)),
+        }
+    }
+}
+
+/// Returns the first date in `text` written as `YYYY-MM-DD`, `YYYY_MM_DD` or 
+/// `YYYYMMDD`, e.g. in a worksheet named `2024-03-05 threshold`.
+pub fn find_date(text: &str) -> Option<Date> {
+    let bytes = text.as_bytes();
+    let number = |range: std::ops::Range<usize>| text.get(range)
+        .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| digits.parse().ok());
+    (0..bytes.len()).find_map(|i| {
+        // the year must not be the end of a longer number
+        if i > 0 && bytes[i - 1].is_ascii_digit() {
+            return None;
+        }
+        let year: u32 = number(i..i + 4)?;
+        let (month, day, end) = match bytes.get(i + 4) {
+            Some(b'-') | Some(b'_') if bytes.get(i + 7) == bytes.get(i + 4) => 
+                (number(i + 5..i + 7)?, number(i + 8..i + 10)?, i + 10),
+            _ => (number(i + 4..i + 6)?, number(i + 6..i + 8)?, i + 8),
+        };
+        if bytes.get(end).is_some_and(u8::is_ascii_digit) {
+            return None;
+        }
+        Date::new(year as i32, month, day)
+    })
+}
+
+/// Returns the date next to a `DATE` label in the header of the worksheet, 
+/// either a date cell or text containing a date (see `find_date`). The 
+/// header ends at the data of the `columns`.
+pub fn sheet_date(range: &Range, columns: DataColumns) -> Option<Date> {
+    for (i, row) in range.rows().enumerate() {
+        if is_data_header(row, columns) || is_data_row(row, columns) {
+            break;
+        }
+        for (j, label) in row.iter().enumerate() {
+            match label {
+                DataType::String(label) if label_matches(label, "date") => (),
+                _ => continue,
+            }
+            return match cell(range, i, j + 1) {
+                DataType::String(text) => find_date(text),
+                // Excel stores dates as days, the fraction is the time
+                DataType::Float(days) => 
+                    Some(Date::from_days(*days as i64 + EXCEL_EPOCH)),
+                DataType::Int(days) => 
+                    Some(Date::from_days(days + EXCEL_EPOCH)),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+/// Reads the FTP from a config file shared by several workbooks. It either 
+/// contains just the number or, like a `toml` file, an `ftp = <watts>` line. 
+/// Comments start with `#`.
+pub fn parse_ftp_config(config: &str) -> Result<f64, String> {
+    let value = config.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .find_map(|line| match line.split_once('=') {
+            Some((key, value)) if key.trim().eq_ignore_ascii_case("ftp") => 
+                Some(value.trim()),
+            Some(_) => None,
+            None => Some(line),
+        })
+        .ok_or("no FTP found")?;
+    match value.parse() {
+        Ok(ftp) if ftp > 0.0 => Ok(ftp),
+        _ => Err(format!("invalid FTP '{}'", value)),
+    }
+}
+
+/// Returns whether `name` matches the glob `pattern`, where `*` matches any 
+/// number of characters and `?` a single one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => 
+                (0..=name.len()).any(|i| matches(rest, &name[i..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => 
+                name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// FTPs of the worksheets of a workbook shared by several athletes, see 
+/// `FtpMap::from_str`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FtpMap {
+    /// Worksheet names or glob patterns (see `glob_match`) with their FTP, in 
+    /// the order of the file
+    pub entries: Vec<(String, f64)>,
+    /// FTP of the worksheets which match none of the entries
+    pub default: Option<f64>,
+}
+
+impl FtpMap {
+    /// Returns the FTP of `worksheet`: the one of its exact name, otherwise 
+    /// the one of the first matching pattern, otherwise the default.
+    pub fn ftp(&self, worksheet: &str) -> Option<f64> {
+        self.entries.iter()
+            .find(|(name, _)| name == worksheet)
+            .or_else(|| self.entries.iter()
+                     .find(|(pattern, _)| glob_match(pattern, worksheet)))
+            .map(|&(_, ftp)| ftp)
+            .or(self.default)
+    }
+}
+
+impl FromStr for FtpMap {
+    type Err = String;
+
+    /// Reads one `<worksheet> = <watts>` line per worksheet, like a `toml` 
+    /// file, or `<worksheet>,<watts>` like a `csv` file. The worksheet may be 
+    /// quoted and contain the glob patterns `*` and `?`, the key `default` 
+    /// sets the FTP of all other worksheets. Lines starting with `#` are 
+    /// comments, since worksheet names may contain `#` elsewhere.
+    fn from_str(map: &str) -> Result<Self, Self::Err> {
+        let mut ftp_map = FtpMap::default();
+        for (i, line) in map.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.rsplit_once(['=', ','])
+                .ok_or_else(|| format!("line {}: expected <worksheet> = \
+                                        <watts>", i + 1))?;
+            let ftp = match value.trim().parse() {
+                Ok(ftp) if ftp > 0.0 => ftp,
+                _ => return Err(format!("line {}: invalid FTP '{}'", i + 1, 
+                                        value.trim())),
+            };
+            match key.trim() {
+                "default" => ftp_map.default = Some(ftp),
+                key => {
+                    let name = key.strip_prefix('"')
+                        .and_then(|key| key.strip_suffix('"'))
+                        .unwrap_or(key);
+                    ftp_map.entries.push((name.to_string(), ftp));
+                },
+            }
+        }
+        Ok(ftp_map)
+    }
+}
+
+/// Rectangle of cells of a worksheet, e.g. `$A$1:$B$3`, given by the 0-based 
+/// row and column of its first and last cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellArea {
+    pub first: (usize, usize),
+    pub last: (usize, usize),
+}
+
+impl CellArea {
+    fn contains(&self, row: usize, col: usize) -> bool {
+        (self.first.0..=self.last.0).contains(&row) 
+            && (self.first.1..=self.last.1).contains(&col)
+    }
+}
+
+impl FromStr for CellArea {
+    type Err = String;
+
+    /// Parses a cell (`B2`) or an area (`A1:B3`) in the A1 notation, `$` 
+    /// signs are ignored.
+    fn from_str(area: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid cell area '{}'", area);
+        let cell = |cell: &str| {
+            let cell = cell.replace('$', "");
+            let digits = cell.find(|c: char| c.is_ascii_digit())
+                .filter(|&digits| digits > 0)
+                .ok_or_else(invalid)?;
+            let col = parse_column(&cell[..digits]).map_err(|_| invalid())?;
+            match cell[digits..].parse::<usize>() {
+                Ok(row) if row > 0 => Ok((row - 1, col)),
+                _ => Err(invalid()),
+            }
+        };
+        let (first, last) = match area.split_once(':') {
+            Some((first, last)) => (cell(first)?, cell(last)?),
+            None => (cell(area)?, cell(area)?),
+        };
+        Ok(CellArea { 
+            first: (first.0.min(last.0), first.1.min(last.1)), 
+            last: (first.0.max(last.0), first.1.max(last.1)),
+        })
+    }
+}
+
+/// Name defined in a workbook for an area of a worksheet. Names of the 
+/// workbook are valid on every worksheet, names of a worksheet (`scope`) 
+/// only on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefinedName {
+    pub name: String,
+    /// Worksheet the name is defined for, `None` for the whole workbook
+    pub scope: Option<String>,
+    /// Worksheet and cells the name refers to
+    pub sheet: String,
+    pub area: CellArea,
+}
+
+/// Reads the names defined in the `xlsx` workbook at `path`, see 
+/// `parse_defined_names`. Legacy `xls` workbooks aren't supported.
+pub fn read_defined_names(path: &Path) -> io::Result<Vec<DefinedName>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut workbook = String::new();
+    archive.by_name("xl/workbook.xml")?.read_to_string(&mut workbook)?;
+    Ok(parse_defined_names(&workbook))
+}
+
+/// Returns the names defined in the `workbook.xml` part of an `xlsx` file 
+/// which refer to a single area of cells. Names of formulas, constants or 
+/// several areas are skipped.
+pub fn parse_defined_names(workbook: &str) -> Vec<DefinedName> {
+    let sheets: Vec<String> = xml_elements(workbook, "sheet").into_iter()
+        .filter_map(|(attributes, _)| xml_attribute(attributes, "name"))
+        .collect();
+    xml_elements(workbook, "definedName").into_iter()
+        .filter_map(|(attributes, reference)| {
+            let name = xml_attribute(attributes, "name")?;
+            let scope = match xml_attribute(attributes, "localSheetId") {
+                Some(id) => Some(sheets.get(id.parse::<usize>().ok()?)?
+                                 .clone()),
+                None => None,
+            };
+            let reference = xml_unescape(reference);
+            let (sheet, area) = reference.trim().rsplit_once('!')?;
+            // a single quote in a quoted name is escaped by another one
+            let sheet = match sheet.strip_prefix('\'') {
+                Some(quoted) => Some(quoted.strip_suffix('\'')?)
+                    .filter(|quoted| !quoted.replace("''", "").contains('\''))?
+                    .replace("''", "'"),
+                None => Some(sheet).filter(|sheet| !sheet.contains(['!', ',']))?
+                    .to_string(),
+            };
+            Some(DefinedName { name, scope, sheet, area: area.parse().ok()? })
+        })
+        .collect()
+}
+
+/// Returns the area of the name defined for `worksheet`, preferring a name 
+/// of the worksheet over one of the workbook referring to it. Like in Excel, 
+/// the case of the name doesn't matter.
+pub fn named_area(names: &[DefinedName], worksheet: &str, name: &str) 
+    -> Option<CellArea> {
+    let named = |defined: &&DefinedName| 
+        defined.name.eq_ignore_ascii_case(name) && defined.sheet == worksheet;
+    names.iter()
+        .filter(named)
+        .find(|defined| defined.scope.as_deref() == Some(worksheet))
+        .or_else(|| names.iter()
+                 .filter(named)
+                 .find(|defined| defined.scope.is_none()))
+        .map(|defined| defined.area)
+}
+
+/// Returns the attributes and the text of every `tag` element in `xml`, just 
+/// enough to read the workbook part of `xlsx` files.
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<(&'a str, &'a str)> {
+    let (open, close) = (format!("<{}", tag), format!("</{}>", tag));
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // e.g. <definedNames> around the <definedName> elements
+        if !rest.starts_with(|c: char| c.is_whitespace() || c == '>' 
+                             || c == '/') {
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let (attributes, empty) = match rest[..end].strip_suffix('/') {
+            Some(attributes) => (attributes, true),
+            None => (&rest[..end], false),
+        };
+        rest = &rest[end + 1..];
+        let text = if empty {
+            ""
+        } else {
+            let (text, after) = rest.split_at(rest.find(&close)
+                                              .unwrap_or(rest.len()));
+            rest = after;
+            text
+        };
+        elements.push((attributes, text));
+    }
+    elements
+}
+
+/// Returns the unescaped value of the attribute `name` of an element.
+fn xml_attribute(attributes: &str, name: &str) -> Option<String> {
+    let key = format!("{}=\"", name);
+    let start = attributes.match_indices(&key)
+        .map(|(start, _)| start)
+        .find(|&start| attributes[..start].ends_with(char::is_whitespace))?
+        + key.len();
+    let length = attributes[start..].find('"')?;
+    Some(xml_unescape(&attributes[start..start + length]))
+}
+
+/// Replaces the predefined entities of XML by their characters.
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Rearranges the cells of a worksheet so that the `metadata` area is 
+/// followed by the `data` area, e.g. for templates which keep them somewhere 
+/// else than the header rows above the data points. A missing area is the 
+/// rest of the worksheet, so only its empty rows are left out. The result is 
+/// parsed like any other worksheet by `parse_range`, the data columns count 
+/// from the first column of the `data` area.
+/// The positions of the cells are 1-based, like the ones read from `xlsx` 
+/// files.
+pub fn named_range(range: &Range, metadata: Option<CellArea>, 
+                   data: Option<CellArea>) -> Range {
+    if metadata.is_none() && data.is_none() {
+        return range.clone();
+    }
+    let (top, left) = range.get_position();
+    let (top, left) = (top.saturating_sub(1) as usize, 
+                       left.saturating_sub(1) as usize);
+    let (height, width) = range.get_size();
+    let worksheet = CellArea { 
+        first: (top, left), 
+        last: ((top + height).saturating_sub(1), 
+               (left + width).saturating_sub(1)),
+    };
+    let value = |row: usize, col: usize| {
+        if height > 0 && width > 0 && worksheet.contains(row, col) {
+            range.get_value(row - top, col - left).clone()
+        } else {
+            DataType::Empty
+        }
+    };
+    let rows = |area: Option<CellArea>, other: Option<CellArea>| {
+        let area = area.unwrap_or(worksheet);
+        (area.first.0..=area.last.0)
+            .map(|row| (area.first.1..=area.last.1)
+                 .map(|col| match other {
+                     Some(other) if other.contains(row, col) => DataType::Empty,
+                     _ => value(row, col),
+                 })
+                 .collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    let mut cells: Vec<Vec<DataType>> = rows(metadata, data).into_iter()
+        .filter(|row| row.iter().any(|value| *value != DataType::Empty))
+        .collect();
+    cells.extend(rows(data, metadata));
+
+    let width = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let mut named = Range::new((1, 1), (cells.len(), width));
+    for (i, row) in cells.into_iter().enumerate() {
+        for (j, value) in row.into_iter().enumerate() {
+            named.set_value((i as u32 + 1, j as u32 + 1), value);
+        }
+    }
+    named
+}
+
+/// Parses a single worksheet of the workbook into a `Workout`, see 
+/// `parse_range`.
+pub fn parse_workout(workbook: &mut Excel, worksheet: &str, 
+                     options: &ParseOptions) -> Result<Workout, ParseError> {
+
+    let range = workbook.worksheet_range(worksheet)
+        .map_err(|_| ParseError::SheetNotFound)?;
+    parse_range(&range, options)
+}
+
+/// Parses the cells of a worksheet into a `Workout`. The header contains FTP, 
+/// file name, description and optionally the units, each in the cell right of 
+/// its label (see `scan_header`). The data points start below the 
+/// `MINUTES`/`TIME` row. Empty rows between them are skipped with a warning, 
+/// with `strict` the data ends at the first empty row and any other warning 
+/// is an error. Rows starting with text are comments and skipped as well, 
+/// text right of the data columns labels the interval starting in the row.
+pub fn parse_range(range: &Range, options: &ParseOptions) 
+    -> Result<Workout, ParseError> {
+    parse_cells(range, options, None)
+}
+
+/// Checks the cells of a worksheet like `parse_range`, but doesn't stop at 
+/// the first problem, e.g. for linting templates. Returns all errors and 
+/// warnings (as `ParseError::Strict`), none if the worksheet converts 
+/// cleanly. Invalid rows are left out, so their neighbors may be reported as 
+/// well.
+pub fn validate_range(range: &Range, options: &ParseOptions) 
+    -> Vec<ParseError> {
+    let mut problems = Vec::new();
+    // empty rows end the data with strict, they're worth a warning here
+    let options = ParseOptions { strict: false, ..options.clone() };
+    let error = match parse_cells(range, &options, Some(&mut problems)) {
+        Ok(workout) => {
+            problems.extend(workout.warnings.into_iter()
+                            .map(|warning| ParseError::Strict { warning }));
+            None
+        },
+        Err(e) => Some(e),
+    };
+    if let Some(error) = error {
+        if !problems.contains(&error) {
+            problems.push(error);
+        }
+    }
+    problems
+}
+
+/// Returns the error, or collects it into `problems` to carry on validating. 
+/// Every problem is collected once, e.g. a missing FTP of absolute watts.
+fn problem(problems: &mut Option<&mut Vec<ParseError>>, error: ParseError) 
+    -> Result<(), ParseError> {
+    match problems {
+        Some(problems) => {
+            if !problems.contains(&error) {
+                problems.push(error);
+            }
+            Ok(())
+        },
+        None => Err(error),
+    }
+}
+
+/// Parses the cells of a worksheet for `parse_range` or, collecting the 
+/// `problems`, for `validate_range`.
+fn parse_cells(range: &Range, options: &ParseOptions, 
+               mut problems: Option<&mut Vec<ParseError>>) 
+    -> Result<Workout, ParseError> {
+
+    let mut workout = Workout { 
+        tss_scale: options.tss_scale, 
+        ..Default::default() 
+    };
+    let columns = options.columns;
+    let header = scan_header(range, options.header_rows, columns);
+
+    let ftp = options.ftp.or(header.ftp.and_then(coerce_number));
+    let watts = options.watts || header.watts;
+    match header.file_name {
+        Some(DataType::String(file_name)) => 
+            workout.file_name = file_name.to_string(),
+        _ => problem(&mut problems, ParseError::MissingFileName)?,
+    }
+    let mut description = Vec::new();
+    for value in header.description {
+        match value {
+            DataType::String(line) => description.push(line.trim()),
+            DataType::Empty => (),
+            _ => problem(&mut problems, ParseError::MissingDescription)?,
+        }
+    }
+    workout.description = description.join(" ");
+    match header.units {
+        Some(DataType::String(units)) => match units.parse() {
+            Ok(units) => workout.units = units,
+            Err(_) => problem(&mut problems, ParseError::BadUnits { 
+                value: units.to_string() 
+            })?,
+        },
+        Some(value) => problem(&mut problems, ParseError::BadUnits { 
+            value: format!("{:?}", value) 
+        })?,
+        None => (),
+    }
+    let unit = match header.intensity_unit {
+        Some(DataType::String(unit)) => match unit.parse() {
+            Ok(unit) => unit,
+            Err(_) => {
+                problem(&mut problems, ParseError::BadIntensityUnit { 
+                    value: unit.to_string() 
+                })?;
+                IntensityUnit::Fraction
+            },
+        },
+        Some(DataType::Empty) | None => IntensityUnit::Fraction,
+        Some(value) => {
+            problem(&mut problems, ParseError::BadIntensityUnit { 
+                value: format!("{:?}", value) 
+            })?;
+            IntensityUnit::Fraction
+        },
+    };
+    // finishing the workout would only report it after the data points
+    if problems.is_some() {
+        if let Err(e) = checked_ftp(ftp) {
+            problem(&mut problems, e)?;
+        }
+    }
+    workout.hr_max = options.hr_max.or(header.hr_max.and_then(coerce_number));
+
+    // empty rows are only reported once more data follows
+    let mut empty_rows = Vec::new();
+    // the open `REPEAT` row, its count and first data point, and the time 
+    // the repetitions so far added to the following data points
+    let mut repeat = None;
+    let mut offset = 0.0;
+    for (i, row) in range.rows().enumerate().skip(header.data_start) {
+        if row.iter().all(|c| *c == DataType::Empty) {
+            if options.strict {
+                debug!("data ends in row {}", i + 1);
+                break;
+            }
+            empty_rows.push(i + 1);
+            continue;
+        }
+        // comments start with text in or left of the time column
+        let first = row.iter().position(|c| *c != DataType::Empty)
+            .filter(|&j| j <= columns.time);
+        if let Some(DataType::String(comment)) = first.map(|j| &row[j]) {
+            let comment = comment.trim();
+            if comment.parse::<f64>().is_err() {
+                if let Some(count) = repeat_count(comment) {
+                    if repeat.is_some() || count == 0 {
+                        problem(&mut problems, 
+                                ParseError::BadRepeat { row: i + 1 })?;
+                    } else {
+                        repeat = Some((i + 1, count, 
+                                       workout.workout_data.len()));
+                    }
+                    continue;
+                }
+                if comment.eq_ignore_ascii_case("end repeat") {
+                    let repeated = match repeat.take() {
+                        Some((row, count, start)) => 
+                            repeat_points(&mut workout, row, count, start),
+                        None => Err(ParseError::BadRepeat { row: i + 1 }),
+                    };
+                    match repeated {
+                        Ok(added) => offset += added,
+                        Err(e) => problem(&mut problems, e)?,
+                    }
+                    continue;
+                }
+                debug!("row {}: skipping comment '{}'", i + 1, comment);
+                workout.markers.push((workout.workout_data.len(), 
+                                      comment.to_string()));
+                continue;
+            }
+        }
+        workout.warnings.extend(empty_rows.drain(..)
+                                .map(|row| Warning::EmptyRow { row }));
+        let bad_row = || ParseError::BadRow { 
+            row: i + 1, 
+            cells: row_cells(row) 
+        };
+        // cells right of the data columns, e.g. the units, widen the range
+        let number = |j: usize| row.get(j).and_then(coerce_number);
+        let (time, intensity) = (number(columns.time), 
+                                 number(columns.intensity));
+        let rest = row.get(columns.end()..).unwrap_or_default();
+        let (cadence, rest) = match rest {
+            [cadence, rest @ ..] if header.cadence => (cadence, rest),
+            _ => (&DataType::Empty, rest),
+        };
+        let cadence = match cadence {
+            // cadence is optional for every data point
+            DataType::Empty => Some(None),
+            cadence => coerce_number(cadence).map(Some),
+        };
+        // the text right of the data labels the interval
+        let mut label = Some(None);
+        for cell in rest {
+            match cell {
+                DataType::Empty => (),
+                DataType::String(text) if label == Some(None) => 
+                    label = Some(Some(text.trim().to_string())
+                                 .filter(|text| !text.is_empty())),
+                _ => label = None,
+            }
+        }
+        match (time, intensity, cadence, label) {
+            (Some(time), Some(intensity), Some(cadence), Some(label)) => {
+                let heart_rate = if header.heart_rate {
+                    Some(intensity)
+                } else {
+                    None
+                };
+                let intensity = match heart_rate {
+                    Some(heart_rate) => options.hr_curve.intensity(heart_rate),
+                    None => match relative_intensity(intensity, watts, ftp) {
+                        // absolute watts have no unit to scale
+                        Ok(intensity) if watts => intensity,
+                        Ok(intensity) => unit.fraction(intensity),
+                        Err(e) => {
+                            problem(&mut problems, e)?;
+                            continue;
+                        },
+                    },
+                };
+                let time = options.time_unit.minutes(time) + offset;
+                let point = WorkoutData { 
+                    time, intensity, cadence, heart_rate, label 
+                };
+                if let Err(e) = push_point(&mut workout, i + 1, point, 
+                                           options.strict) {
+                    problem(&mut problems, e)?;
+                }
+            },
+            _ => problem(&mut problems, bad_row())?,
+        }
+    }
+    // the last block may be repeated without `END REPEAT`
+    if let Some((row, count, start)) = repeat {
+        if let Err(e) = repeat_points(&mut workout, row, count, start) {
+            problem(&mut problems, e)?;
+        }
+    }
+
+    finish_workout(workout, ftp, options.strict)
+}
+
+/// Returns the number of repetitions of a `REPEAT <n>` row, e.g. `REPEAT 8` 
+/// or `repeat 8x`.
+fn repeat_count(comment: &str) -> Option<usize> {
+    let mut words = comment.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(repeat), Some(count), None) 
+            if repeat.eq_ignore_ascii_case("repeat") => 
+            count.trim_end_matches(['x', 'X', '×']).parse().ok(),
+        _ => None,
+    }
+}
+
+/// Repeats the data points from `start` on, i.e. the block of the `REPEAT` 
+/// row `row`, until there are `count` of them back to back. Returns the 
+/// time the repetitions added, which the following data points are offset 
+/// by. The block has to consist of whole intervals.
+fn repeat_points(workout: &mut Workout, row: usize, count: usize, 
+                 start: usize) -> Result<f64, ParseError> {
+    let block = workout.workout_data[start..].to_vec();
+    let duration = match (block.first(), block.last()) {
+        (Some(first), Some(last)) 
+            if start.is_multiple_of(2) && block.len().is_multiple_of(2) => 
+            last.time - first.time,
+        _ => return Err(ParseError::BadRepeat { row }),
+    };
+    trace!("row {}: repeating {} data points {} times", row, block.len(), 
+           count);
+    for n in 1..count {
+        workout.workout_data.extend(block.iter().map(|data| WorkoutData { 
+            time: data.time + n as f64 * duration, 
+            ..data.clone() 
+        }));
+    }
+    Ok((count - 1) as f64 * duration)
+}
+
+/// Renders the cells of a worksheet row for `ParseError::BadRow`, leaving out 
+/// the empty cells at its end, e.g. `[Float(10.0), String("easy")]`.
+fn row_cells(row: &[DataType]) -> String {
+    let end = row.iter().rposition(|c| *c != DataType::Empty)
+        .map_or(0, |last| last + 1);
+    format!("{:?}", &row[..end])
+}
+
+/// Returns the `ParseError::BadRow` of the given (1-based) line of a text 
+/// file.
+fn bad_line(row: usize, line: &str) -> ParseError {
+    ParseError::BadRow { row, cells: format!("{:?}", line) }
+}
+
+/// Returns the FTP, unless it's missing or not positive.
+fn checked_ftp(ftp: Option<f64>) -> Result<f64, ParseError> {
+    let ftp = ftp.ok_or(ParseError::MissingFtp)?;
+    // the intensity factor would divide by zero
+    if ftp <= 0.0 || ftp.is_nan() {
+        return Err(ParseError::InvalidFtp { ftp });
+    }
+    Ok(ftp)
+}
+
+/// Converts the value of the intensity column to a fraction of FTP, which 
+/// requires the FTP right away for absolute `watts`.
+fn relative_intensity(value: f64, watts: bool, ftp: Option<f64>) 
+    -> Result<f64, ParseError> {
+    if watts {
+        Ok(value / checked_ftp(ftp)?)
+    } else {
+        Ok(value)
+    }
+}
+
+/// Adds the warning to the workout or, with `strict`, returns it as an error.
+fn warn(workout: &mut Workout, warning: Warning, strict: bool) 
+    -> Result<(), ParseError> {
+    if strict {
+        return Err(ParseError::Strict { warning });
+    }
+    workout.warnings.push(warning);
+    Ok(())
+}
+
+/// Checks a data point read from the given (1-based) row and appends it to 
+/// the workout. Negative intensities are raised to 0.
+fn push_point(workout: &mut Workout, row: usize, mut point: WorkoutData, 
+              strict: bool) -> Result<(), ParseError> {
+    trace!("row {}: {} min at {}", row, point.time, point.intensity);
+    // same times are fine, that's how steps are written
+    if workout.workout_data.last()
+        .is_some_and(|previous| point.time < previous.time) {
+        return Err(ParseError::TimeNotIncreasing { row });
+    }
+    if point.intensity > MAX_INTENSITY {
+        warn(workout, Warning::HighIntensity { 
+            row, 
+            intensity: point.intensity,
+        }, strict)?;
+    }
+    if point.intensity < 0.0 {
+        warn(workout, Warning::NegativeIntensity { 
+            row, 
+            intensity: point.intensity,
+        }, strict)?;
+        point.intensity = 0.0;
+    }
+    workout.workout_data.push(point);
+    Ok(())
+}
+
+/// Pairs up the data points into intervals and calculates `TSS`, normalized 
+/// power and the time in zones with the given FTP.
+fn finish_workout(mut workout: Workout, ftp: Option<f64>, strict: bool) 
+    -> Result<Workout, ParseError> {
+    let count = workout.workout_data.len();
+    if !count.is_multiple_of(2) {
+        return Err(ParseError::OddDataPoints { count });
+    }
+
+    workout.ftp = checked_ftp(ftp)?;
+
+    // some erg consumers choke on zero-length segments
+    let mut data = std::mem::take(&mut workout.workout_data);
+    for pair in data.chunks_exact_mut(2) {
+        // a label in the end row names the interval as well
+        if pair[0].label.is_none() {
+            pair[0].label = pair[1].label.take();
+        }
+        if pair[1].time == pair[0].time {
+            warn(&mut workout, Warning::ZeroDuration { time: pair[0].time }, 
+                 strict)?;
+        } else {
+            workout.workout_data.extend_from_slice(pair);
+        }
+    }
+    match workout.workout_data.last() {
+        Some(last) if last.intensity > COOLDOWN_INTENSITY => {
+            let intensity = last.intensity;
+            warn(&mut workout, Warning::NoCooldown { intensity }, strict)?
+        },
+        _ => (),
+    }
+
+    workout.recalculate();
+    Ok(workout)
+}
+
+/// Parses a workout from `csv`. The header consists of `FTP=`, `NAME=` 
+/// (the output file name), `DESC=` and optionally `UNITS=` lines, followed by
+/// one `time,intensity`, `time,intensity,cadence` or 
+/// `time,intensity,cadence,label` line per data point. Empty lines are 
+/// ignored. If `options.ftp` is given, it replaces the FTP of the header.
+pub fn parse_workout_csv(csv: &str, options: &ParseOptions) 
+    -> Result<Workout, ParseError> {
+
+    let mut workout = Workout { 
+        tss_scale: options.tss_scale, 
+        ..Default::default() 
+    };
+    let mut csv_ftp = None;
+    let mut file_name = None;
+
+    for (i, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if !workout.workout_data.is_empty() {
+                return Err(bad_line(i + 1, line));
+            }
+            let value = value.trim();
+            match key.trim().to_ascii_uppercase().as_str() {
+                "FTP" => csv_ftp = Some(value.parse()
+                    .map_err(|_| ParseError::MissingFtp)?),
+                "NAME" => file_name = Some(value.to_string()),
+                "DESC" => workout.description = value.to_string(),
+                "UNITS" => workout.units = value.parse()
+                    .map_err(|_| ParseError::BadUnits { value: value.into() })?,
+                _ => return Err(bad_line(i + 1, line)),
+            }
+            continue;
+        }
+
+        let mut columns = line.split(',').map(str::trim);
+        let mut number = || columns.next().map(str::parse::<f64>);
+        let (time, intensity) = (number(), number());
+        let cadence = match columns.next() {
+            None | Some("") => Ok(None),
+            Some(cadence) => cadence.parse().map(Some),
+        };
+        let label = columns.next().filter(|label| !label.is_empty())
+            .map(str::to_string);
+        match (time, intensity, cadence, columns.next()) {
+            (Some(Ok(time)), Some(Ok(intensity)), Ok(cadence), None) => {
+                let intensity = relative_intensity(
+                    intensity, options.watts, options.ftp.or(csv_ftp))?;
+                let time = options.time_unit.minutes(time);
+                push_point(&mut workout, i + 1, 
+                           WorkoutData { time, intensity, cadence, 
+                                         heart_rate: None, label }, 
+                           options.strict)?
+            },
+            _ => return Err(bad_line(i + 1, line)),
+        }
+    }
+
+    workout.file_name = file_name.ok_or(ParseError::MissingFileName)?;
+    finish_workout(workout, options.ftp.or(csv_ftp), options.strict)
+}
+
+/// Relative tolerance for the `TSS` of a workout read back from its `erg` 
+/// file, which differs slightly because of the rounded watts and times
+pub const VERIFY_TOLERANCE: f64 = 0.01;
+
+/// Parses an `erg` file as written by `write_erg`, e.g. to check the written 
+/// file or to convert it into another format. The time column may contain 
+/// decimal minutes and `mm:ss`. Since consecutive data points form a 
+/// continuous curve, every pair of them with a duration becomes an interval 
+/// (and a pair of data points of the workout), so interpolated ramps are 
+/// parsed as well. `;` lines in the data label the next interval, other 
+/// lines of the header without a value and sections after the data, e.g. 
+/// `[COURSE TEXT]`, are ignored. If `options.ftp` is given, it replaces the 
+/// FTP of the header and the watts are relative to it.
+pub fn parse_erg(erg: &str, options: &ParseOptions) 
+    -> Result<Workout, ParseError> {
+    let mut workout = Workout { 
+        tss_scale: options.tss_scale, 
+        ..Default::default() 
+    };
+    let mut ftp = None;
+    let mut file_name = None;
+    let (mut in_data, mut after_data) = (false, false);
+    let mut label = None;
+
+    for (i, line) in erg.lines().enumerate() {
+        let line = line.trim();
+        match line {
+            _ if after_data => continue,
+            "" | "[COURSE HEADER]" | "[END COURSE HEADER]" => continue,
+            _ if line.starts_with(';') => {
+                if in_data {
+                    label = Some(line[1..].trim().to_string());
+                }
+                continue;
+            },
+            "[COURSE DATA]" => {
+                in_data = true;
+                continue;
+            },
+            "[END COURSE DATA]" => {
+                after_data = true;
+                continue;
+            },
+            _ => (),
+        }
+        if !in_data {
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim();
+                match key.trim() {
+                    "FTP" => ftp = Some(value.parse()
+                        .map_err(|_| ParseError::MissingFtp)?),
+                    "FILE NAME" => file_name = Some(value.to_string()),
+                    "DESCRIPTION" => workout.description = value.to_string(),
+                    "UNITS" => workout.units = value.parse()
+                        .map_err(|_| ParseError::BadUnits { 
+                            value: value.into() 
+                        })?,
+                    _ => (),
+                }
+            }
+            continue;
+        }
+
+        let mut columns = line.split_whitespace();
+        let time = columns.next().and_then(parse_time);
+        let watts = columns.next().and_then(|watts| watts.parse().ok());
+        match (time, watts, columns.next()) {
+            (Some(time), Some(watts), None) => {
+                let ftp = options.ftp.or(ftp);
+                let intensity = relative_intensity(watts, true, ftp)?;
+                push_point(&mut workout, i + 1, 
+                           WorkoutData { time, intensity, label: label.take(), 
+                                         ..Default::default() }, 
+                           options.strict)?
+            },
+            _ => return Err(bad_line(i + 1, line)),
+        }
+    }
+
+    workout.file_name = file_name.ok_or(ParseError::MissingFileName)?;
+    workout.ftp = checked_ftp(options.ftp.or(ftp))?;
+    // the points in the middle of the curve end one interval and start the 
+    // next one
+    let data = std::mem::take(&mut workout.workout_data);
+    for pair in data.windows(2).filter(|pair| pair[1].time > pair[0].time) {
+        workout.workout_data.push(pair[0].clone());
+        workout.workout_data.push(WorkoutData { 
+            label: None, 
+            ..pair[1].clone() 
+        });
+    }
+    workout.recalculate();
+    Ok(workout)
+}
+
+/// Parses a time in decimal minutes or `mm:ss`, see `format_time`.
+fn parse_time(value: &str) -> Option<f64> {
+    match value.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes: f64 = minutes.parse().ok()?;
+            let seconds: f64 = seconds.parse().ok()?;
+            Some(minutes + seconds / 60.0)
+        },
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, 
+                "{} != {}", actual, expected);
+    }
+
+    fn point(time: f64, intensity: f64) -> WorkoutData {
+        WorkoutData { time, intensity, ..Default::default() }
+    }
+
+    /// Builds a worksheet from the given rows
+    fn sheet(rows: &[Vec<DataType>]) -> Range {
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut range = Range::new((0, 0), (rows.len(), width));
+        for (i, row) in rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                range.set_value((i as u32, j as u32), value.clone());
+            }
+        }
+        range
+    }
+
+    fn text(value: &str) -> DataType {
+        DataType::String(value.to_string())
+    }
+
+    #[test]
+    fn steady_interval() {
+        let interval = Interval::new(&point(0.0, 0.8), &point(30.0, 0.8), 
+                                     250.0);
+        assert_close(interval.duration, 30.0);
+        assert_close(interval.watt, 200.0);
+        assert_close(interval.intensity_factor, 0.8);
+        // half an hour at 80% FTP: 0.5 * 0.8^2 * 100
+        assert_close(interval.tss, 32.0);
+    }
+
+    #[test]
+    fn scaled_tss() {
+        let options = ParseOptions { 
+            tss_scale: Some(50.0), 
+            ..Default::default() 
+        };
+        let csv = "FTP=200\nNAME=test.erg\n0,1.0\n60,1.0\n";
+        let workout = parse_workout_csv(csv, &options).unwrap();
+        assert_close(workout.tss, 50.0);
+        assert_close(workout.intervals[0].tss, 50.0);
+        let workout = parse_workout_csv(csv, &ParseOptions::default()).unwrap();
+        assert_close(workout.tss, TSS_SCALE);
+    }
+
+    #[test]
+    fn ramped_interval() {
+        let interval = Interval::new(&point(0.0, 0.5), &point(60.0, 1.0), 
+                                     200.0);
+        assert_close(interval.duration, 60.0);
+        assert_close(interval.watt, 150.0);
+        // (0.5^2 + 0.5 * 1.0 + 1.0^2) / 3 = 0.5833 instead of 0.75^2
+        assert_close(interval.intensity_factor, (7.0f64 / 12.0).sqrt());
+        assert_close(interval.tss, 700.0 / 12.0);
+    }
+
+    #[test]
+    fn steep_ramp() {
+        let interval = Interval::new(&point(0.0, 0.2), &point(10.0, 2.0), 
+                                     300.0);
+        // midpoint sum over 0.6 s slices
+        let steps = 1000;
+        let slice = interval.duration / steps as f64;
+        let tss: f64 = (0..steps)
+            .map(|i| {
+                let intensity = 0.2 + 1.8 * (i as f64 + 0.5) / steps as f64;
+                slice / 60.0 * intensity * intensity * 100.0
+            })
+            .sum();
+        assert!((interval.tss - tss).abs() < 1e-4, "{} != {}", 
+                interval.tss, tss);
+    }
+
+    #[test]
+    fn coerce_numbers() {
+        assert_eq!(coerce_number(&DataType::Float(0.5)), Some(0.5));
+        assert_eq!(coerce_number(&DataType::Int(250)), Some(250.0));
+        assert_eq!(coerce_number(&text(" 250 ")), Some(250.0));
+        assert_eq!(coerce_number(&text("FTP")), None);
+        assert_eq!(coerce_number(&DataType::Empty), None);
+    }
+
+    #[test]
+    fn integer_ftp() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("DESCRIPTION"), text("Test")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_close(workout.ftp, 200.0);
+
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        let erg = String::from_utf8(erg).unwrap();
+        assert!(erg.contains("0.00\t100\n10.00\t100\n"), "{}", erg);
+    }
+
+    #[test]
+    fn zero_ftp() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(0)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+        ]);
+        assert_eq!(parse_range(&range, &ParseOptions::default()).unwrap_err(), 
+                   ParseError::InvalidFtp { ftp: 0.0 });
+    }
+
+    #[test]
+    fn absolute_watts() {
+        let rows = |label: &str, first: f64, second: f64| sheet(&[
+            vec![text("FTP"), DataType::Int(250)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text(label)],
+            vec![DataType::Float(0.0), DataType::Float(first)],
+            vec![DataType::Float(20.0), DataType::Float(first)],
+            vec![DataType::Float(20.0), DataType::Float(second)],
+            vec![DataType::Float(30.0), DataType::Float(first)],
+        ]);
+        let options = ParseOptions::default();
+        let relative = parse_range(&rows("INTENSITY", 0.6, 1.1), &options)
+            .unwrap();
+        let absolute = parse_range(&rows("WATTS", 150.0, 275.0), &options)
+            .unwrap();
+        assert_close(absolute.workout_data[2].intensity, 1.1);
+        assert_close(absolute.tss, relative.tss);
+        assert_close(absolute.intervals[1].watt, relative.intervals[1].watt);
+
+        let options = ParseOptions { watts: true, ..Default::default() };
+        let flag = parse_range(&rows("INTENSITY", 150.0, 275.0), &options)
+            .unwrap();
+        assert_close(flag.tss, relative.tss);
+    }
+
+    #[test]
+    fn percentage_intensity() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(85.0)],
+            vec![DataType::Float(10.0), DataType::Float(0.85)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.warnings, 
+                   vec![Warning::HighIntensity { row: 4, intensity: 85.0 }, 
+                        Warning::NoCooldown { intensity: 0.85 }]);
+    }
+
+    #[test]
+    fn intensity_units() {
+        let rows = |unit: &str, low: f64, high: f64| sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("INTENSITY UNIT"), text(unit)],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(low)],
+            vec![DataType::Float(10.0), DataType::Float(low)],
+            vec![DataType::Float(10.0), DataType::Float(high)],
+            vec![DataType::Float(20.0), DataType::Float(high)],
+        ]);
+        let options = ParseOptions::default();
+        let watts = |workout: &Workout| workout.intervals.iter()
+            .map(|interval| interval.watt)
+            .collect::<Vec<_>>();
+        let fraction = parse_range(&rows("fraction", 0.6, 0.85), &options)
+            .unwrap();
+        let percent = parse_range(&rows("Percent", 60.0, 85.0), &options)
+            .unwrap();
+        assert_eq!(watts(&percent), watts(&fraction));
+        assert_eq!(watts(&percent), [120.0, 170.0]);
+        assert_close(percent.tss, fraction.tss);
+        assert!(percent.warnings.iter()
+                .all(|w| !matches!(w, Warning::HighIntensity { .. })));
+
+        assert_eq!(parse_range(&rows("watts", 0.6, 0.85), &options)
+                   .unwrap_err(), 
+                   ParseError::BadIntensityUnit { value: "watts".into() });
+    }
+
+    #[test]
+    fn messy_labels() {
+        let range = sheet(&[
+            vec![text(" ftp "), DataType::Int(200)],
+            vec![text("File Name "), text("test.erg")],
+            vec![text("\tDescription"), text("Easy")],
+            vec![text("units "), text(" Metric ")],
+            vec![text("Intensity Unit"), text("PERCENT ")],
+            vec![text("Date "), text("2024-03-05")],
+            vec![text(" Minutes"), text("watts ")],
+            vec![DataType::Float(0.0), DataType::Float(100.0)],
+            vec![DataType::Float(10.0), DataType::Float(100.0)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.ftp, 200.0);
+        assert_eq!(workout.file_name, "test.erg");
+        assert_eq!(workout.description, "Easy");
+        assert_eq!(workout.units, Units::Metric);
+        // absolute watts aren't scaled by the intensity unit
+        assert_close(workout.intervals[0].watt, 100.0);
+        assert_eq!(sheet_date(&range, DataColumns::default()), 
+                   Some(Date::new(2024, 3, 5).unwrap()));
+
+        assert!(label_matches("Overview ", "overview"));
+        assert!(label_matches(" OVERVIEW", " Overview"));
+        assert!(label_matches("ÜBERSICHT", "übersicht"));
+        assert!(!label_matches("Overview 2", "Overview"));
+    }
+
+    #[test]
+    fn heart_rate_sheet() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("HR MAX"), DataType::Int(190)],
+            vec![text("MINUTES"), text("HR")],
+            vec![DataType::Float(0.0), DataType::Float(0.7)],
+            vec![DataType::Float(10.0), DataType::Float(0.7)],
+            vec![DataType::Float(10.0), DataType::Float(0.6)],
+            vec![DataType::Float(20.0), DataType::Float(0.6)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.hr_max, Some(190.0));
+        assert_eq!(workout.workout_data[0].heart_rate, Some(0.7));
+        assert!((workout.workout_data[0].intensity - 0.5).abs() < 1e-9);
+        assert!((workout.workout_data[2].intensity - 0.25).abs() < 1e-9);
+
+        let mut erg = Vec::new();
+        write_hr_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        let erg = String::from_utf8(erg).unwrap();
+        assert!(erg.contains("MINUTES HEARTRATE\n"));
+        assert!(erg.contains("0.00\t133\n10.00\t133\n10.00\t114\n"));
+
+        let options = ParseOptions { 
+            hr_curve: "0.6,1.0".parse().unwrap(), 
+            hr_max: Some(180.0), 
+            ..Default::default() 
+        };
+        let workout = parse_range(&range, &options).unwrap();
+        assert_eq!(workout.hr_max, Some(180.0));
+        assert!((workout.workout_data[0].intensity - 0.25).abs() < 1e-9);
+        assert!("0.9,0.5".parse::<HeartRateCurve>().is_err());
+        assert!("0.5".parse::<HeartRateCurve>().is_err());
+
+        // power based workouts have no heart rates
+        let workout = parse_workout_csv("FTP=200\nNAME=a.erg\n0,0.5\n10,0.5\n", 
+                                        &options).unwrap();
+        let e = write_hr_erg(&workout, &WriteOptions::default(), 
+                             &mut Vec::new()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn padded_duration() {
+        let options = ParseOptions::default();
+        let csv = "FTP=200\nNAME=a.erg\n0,0.5\n10,0.5\n10,0.9\n40,0.9\n\
+                   40,0.5\n50,0.5\n";
+        let mut workout = parse_workout_csv(csv, &options).unwrap();
+        let tss = workout.tss;
+        workout.fit_duration(60.0, false);
+        assert_eq!(workout.duration(), 60.0);
+        assert_eq!(workout.intervals.len(), 3);
+        assert_eq!(workout.workout_data.last().unwrap().time, 60.0);
+        assert!(workout.tss > tss);
+        assert!(workout.warnings.is_empty());
+
+        // no steady cooldown to extend
+        let csv = "FTP=200\nNAME=a.erg\n0,0.5\n10,0.5\n10,0.9\n40,0.9\n";
+        let mut workout = parse_workout_csv(csv, &options).unwrap();
+        workout.warnings.clear();
+        workout.fit_duration(60.0, false);
+        assert_eq!(workout.duration(), 60.0);
+        let last = &workout.intervals[2];
+        assert_eq!((last.duration, last.start_intensity, last.end_intensity), 
+                   (20.0, COOLDOWN_INTENSITY, COOLDOWN_INTENSITY));
+    }
+
+    #[test]
+    fn overshot_duration() {
+        let csv = "FTP=200\nNAME=a.erg\n0,0.5\n10,0.5\n10,0.9\n40,0.9\n\
+                   40,0.5\n50,0.5\n";
+        let mut workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let tss = workout.tss;
+        workout.fit_duration(45.0, false);
+        assert_eq!(workout.duration(), 50.0);
+        assert_eq!(workout.tss, tss);
+        assert_eq!(workout.warnings, 
+                   vec![Warning::OverTarget { minutes: 50.0, target: 45.0 }]);
+
+        workout.fit_duration(35.0, true);
+        assert_eq!(workout.duration(), 35.0);
+        assert_eq!(workout.intervals.len(), 2);
+        assert_eq!(workout.workout_data.last().unwrap().time, 35.0);
+        assert!(workout.tss < tss);
+    }
+
+    #[test]
+    fn zwo_intervals() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("DESCRIPTION"), text("Warm up & ramp")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.6)],
+            vec![DataType::Float(12.5), DataType::Float(1.2)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        let ramp = &workout.intervals[1];
+        assert_close(ramp.start_watt, 120.0);
+        assert_close(ramp.end_watt, 240.0);
+        assert_close(ramp.watt, 180.0);
+        let mut zwo = Vec::new();
+        write_zwo(&workout, &mut zwo).unwrap();
+        assert_eq!(String::from_utf8(zwo).unwrap(), "\
+<workout_file>
+    <name>Warm up &amp; ramp</name>
+    <description>Warm up &amp; ramp</description>
+    <sportType>bike</sportType>
+    <workout>
+        <SteadyState Duration=\"600\" Power=\"0.5\"/>
+        <Ramp Duration=\"150\" PowerLow=\"0.6\" PowerHigh=\"1.2\"/>
+    </workout>
+</workout_file>
+");
+    }
+
+    #[test]
+    fn interval_labels() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5), DataType::Empty],
+            vec![DataType::Float(10.0), DataType::Float(0.5), DataType::Empty],
+            vec![DataType::Float(10.0), DataType::Float(1.0), 
+                 text("Threshold <1>")],
+            vec![DataType::Float(15.0), DataType::Float(1.0), DataType::Empty],
+            vec![DataType::Float(15.0), DataType::Float(0.5), DataType::Empty],
+            // the label may be in the end row as well
+            vec![DataType::Float(20.0), DataType::Float(0.5), text("Recovery")],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        let labels: Vec<_> = workout.intervals.iter()
+            .map(|interval| interval.label.as_deref())
+            .collect();
+        assert_eq!(labels, [None, Some("Threshold <1>"), Some("Recovery")]);
+
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        let erg = String::from_utf8(erg).unwrap();
+        assert!(erg.contains("10.00\t100\n; Threshold <1>\n10.00\t200\n"));
+        assert!(erg.contains("15.00\t200\n; Recovery\n15.00\t100\n"));
+        // the labels are read back
+        let parsed = parse_erg(&erg, &ParseOptions::default()).unwrap();
+        let labels: Vec<_> = parsed.intervals.into_iter()
+            .filter_map(|interval| interval.label)
+            .collect();
+        assert_eq!(labels, ["Threshold <1>", "Recovery"]);
+
+        let mut zwo = Vec::new();
+        write_zwo(&workout, &mut zwo).unwrap();
+        assert!(String::from_utf8(zwo).unwrap().contains("\
+        <SteadyState Duration=\"600\" Power=\"0.5\"/>
+        <SteadyState Duration=\"300\" Power=\"1\">
+            <textevent timeoffset=\"0\" message=\"Threshold &lt;1&gt;\"/>
+        </SteadyState>
+"));
+
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5,,Warmup\n10,0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(workout.intervals[0].label.as_deref(), Some("Warmup"));
+    }
+
+    /// Reads the steps of a `fit` file written by `write_fit` back: their 
+    /// duration in ms and the low and high end of their power target.
+    fn read_fit_steps(fit: &[u8]) -> Vec<(u32, u32, u32)> {
+        assert_eq!(&fit[8..12], b".FIT");
+        assert_eq!(fit_crc(&fit[..12]).to_le_bytes(), fit[12..14]);
+        let size = u32::from_le_bytes([fit[4], fit[5], fit[6], fit[7]]);
+        let size = size as usize;
+        assert_eq!(fit.len(), 14 + size + 2);
+        // the CRC over the data including its CRC is 0
+        assert_eq!(fit_crc(fit), 0);
+
+        let mut definitions = HashMap::new();
+        let mut steps = Vec::new();
+        let mut i = 14;
+        while i < 14 + size {
+            let (header, local) = (fit[i], fit[i] & 0x0F);
+            i += 1;
+            if header & 0x40 != 0 {
+                let global = u16::from_le_bytes([fit[i + 2], fit[i + 3]]);
+                let fields: Vec<_> = fit[i + 5..i + 5 + 3 * fit[i + 4] as usize]
+                    .chunks(3)
+                    .map(|field| (field[0], field[1] as usize))
+                    .collect();
+                i += 5 + 3 * fields.len();
+                definitions.insert(local, (global, fields));
+                continue;
+            }
+            let (global, fields) = &definitions[&local];
+            let mut values = HashMap::new();
+            for &(number, size) in fields {
+                values.insert(number, &fit[i..i + size]);
+                i += size;
+            }
+            if *global == FIT_WORKOUT_STEP {
+                let value = |number| u32::from_le_bytes(
+                    values[&number].try_into().unwrap());
+                steps.push((value(2), value(5), value(6)));
+            }
+        }
+        steps
+    }
+
+    #[test]
+    fn fit_round_trip() {
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5\n10,0.5\n10,0.5\n13,0.8\n\
+                   13,0.9\n18,0.9\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let mut fit = Vec::new();
+        write_fit(&workout, &WriteOptions::default(), &mut fit).unwrap();
+        // the ramp is split into steps of a minute with a range of power
+        assert_eq!(read_fit_steps(&fit), [
+            (600000, 50, 50), (60000, 50, 60), (60000, 60, 70), 
+            (60000, 70, 80), (300000, 90, 90),
+        ]);
+
+        let options = WriteOptions { fit_watts: true, ..Default::default() };
+        let mut fit = Vec::new();
+        write_fit(&workout, &options, &mut fit).unwrap();
+        assert_eq!(read_fit_steps(&fit)[1], (60000, 1100, 1120));
+        assert_eq!(read_fit_steps(&fit)[4], (300000, 1180, 1180));
+    }
+
+    #[test]
+    fn gc_json_segments() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Sweet spot\n\
+                   0,0.5\n10,0.5\n10,0.9\n30.5,0.9\n30.5,0.5\n35,0.7\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let mut json = Vec::new();
+        write_gc_json(&workout, &WriteOptions::default(), &mut json).unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), r#"{
+  "name": "Sweet spot",
+  "workout": [
+    {
+      "duration": 600,
+      "wattage": 100
+    },
+    {
+      "duration": 1230,
+      "wattage": 180
+    },
+    {
+      "duration": 270,
+      "wattage": 120
+    }
+  ]
+}
+"#);
+    }
+
+    #[test]
+    fn time_in_zones() {
+        let data = [
+            point(0.0, 0.5), point(10.0, 0.5), 
+            point(10.0, 1.0), point(15.0, 1.0),
+            point(15.0, 2.0), point(16.0, 2.0),
+        ];
+        let minutes = zone_minutes(&data);
+        let expected = [10.0, 0.0, 0.0, 5.0, 0.0, 0.0, 1.0];
+        for (actual, expected) in minutes.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 1e-6, "{:?}", minutes);
+        }
+    }
+
+    #[test]
+    fn decreasing_time() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.7)],
+            vec![DataType::Float(5.0), DataType::Float(0.7)],
+        ]);
+        assert_eq!(parse_range(&range, &ParseOptions::default()).unwrap_err(), 
+                   ParseError::TimeNotIncreasing { row: 7 });
+    }
+
+    #[test]
+    fn zero_duration_interval() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Test\n\
+                   0,0.5\n10,0.5\n10,1.0\n10,1.0\n10,0.7\n20,0.7\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.workout_data.len(), 4);
+        assert_eq!(workout.intervals.len(), 2);
+        assert_close(workout.intervals[1].watt, 140.0);
+        assert_eq!(workout.warnings, 
+                   vec![Warning::ZeroDuration { time: 10.0 }, 
+                        Warning::NoCooldown { intensity: 0.7 }]);
+    }
+
+    #[test]
+    fn clamped_watts() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Test\n\
+                   0,0.5\n60,0.5\n60,10.0\n61,10.0\n";
+        let mut workout = parse_workout_csv(csv, &ParseOptions::default()).unwrap();
+        let tss = workout.tss;
+        assert_eq!(workout.clamp_watts(400.0), 2);
+        assert_close(workout.workout_data[3].intensity, 2.0);
+        assert_close(workout.intervals[1].watt, 400.0);
+        assert_close(workout.tss, tss);
+
+        workout.recalculate();
+        assert_close(workout.tss, 25.0 + 2.0 * 2.0 * 100.0 / 60.0);
+    }
+
+    #[test]
+    fn floored_watts() {
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5\n10,0.5\n10,0.15\n15,0.15\n\
+                   15,0.2\n20,0.1\n";
+        let mut workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let tss = workout.tss;
+        // the warmup stays, the recovery and the ramp down are raised
+        assert_eq!(workout.floor_watts(50.0), 4);
+        assert_close(workout.workout_data[0].intensity, 0.5);
+        assert_close(workout.workout_data[5].intensity, 0.25);
+        assert_close(workout.intervals[1].watt, 50.0);
+        assert_close(workout.intervals[2].end_intensity, 0.25);
+        assert_close(workout.tss, tss);
+
+        workout.recalculate();
+        assert_close(workout.tss, 
+                     (10.0 * 0.25 + 10.0 * 0.25 * 0.25) * 100.0 / 60.0);
+        assert_eq!(workout.floor_watts(50.0), 0);
+    }
+
+    #[test]
+    fn rounded_watts() {
+        assert_eq!(round_watts(199.5, Rounding::Nearest), 200);
+        assert_eq!(round_watts(199.49, Rounding::Nearest), 199);
+        assert_eq!(round_watts(199.5, Rounding::Truncate), 199);
+        assert_eq!(round_watts(199.9, Rounding::Truncate), 199);
+        assert_eq!(round_watts(199.5, Rounding::Ceil), 200);
+        assert_eq!(round_watts(199.0, Rounding::Ceil), 199);
+        assert_eq!(round_watts(-0.4, Rounding::Nearest), 0);
+        assert_eq!(round_watts(-3.0, Rounding::Ceil), 0);
+    }
+
+    #[test]
+    fn zero_watts() {
+        let csv = "FTP=200\nNAME=test.erg\n0,0.0\n5,0.0\n5,-0.5\n10,-0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(workout.warnings, vec![
+            Warning::NegativeIntensity { row: 5, intensity: -0.5 },
+            Warning::NegativeIntensity { row: 6, intensity: -0.5 },
+        ]);
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        let erg = String::from_utf8(erg).unwrap();
+        assert!(erg.ends_with("[COURSE DATA]\n0.00\t0\n5.00\t0\n\
+                               5.00\t0\n10.00\t0\n[END COURSE DATA]\n"));
+        let mut mrc = Vec::new();
+        write_mrc(&workout, &WriteOptions::default(), &mut mrc).unwrap();
+        assert!(!String::from_utf8(mrc).unwrap().contains('-'));
+    }
+
+    #[test]
+    fn sheet_order() {
+        assert_eq!(natural_cmp("Week2", "Week10"), Ordering::Less);
+        assert_eq!(natural_cmp("Week10", "Week2"), Ordering::Greater);
+        assert_eq!(natural_cmp("Week2", "Week2"), Ordering::Equal);
+        assert_eq!(natural_cmp("Week02", "Week2"), Ordering::Less);
+        assert_eq!(natural_cmp("Week2", "Week2b"), Ordering::Less);
+        assert_eq!(natural_cmp("W1D10", "W1D9"), Ordering::Greater);
+
+        let sheets = ["Week10", "Overview", "Week2", "Week1"];
+        let sorted = |order: SheetOrder| {
+            let mut sheets: Vec<_> = sheets.iter().map(|s| s.to_string())
+                .collect();
+            order.sort(&mut sheets);
+            sheets
+        };
+        assert_eq!(sorted(SheetOrder::Lexical), 
+                   ["Overview", "Week1", "Week10", "Week2"]);
+        assert_eq!(sorted(SheetOrder::Natural), 
+                   ["Overview", "Week1", "Week2", "Week10"]);
+        assert_eq!(sorted(SheetOrder::Workbook), sheets);
+    }
+
+    #[test]
+    fn ftp_map() {
+        let map: FtpMap = "# athletes of the club\n\
+                           Anna * = 250\n\
+                           \"Anna week 3\" = 260\n\
+                           Ben*,280\n\
+                           default = 200\n".parse().unwrap();
+        // exact names take precedence over patterns, even later ones
+        assert_eq!(map.ftp("Anna week 3"), Some(260.0));
+        assert_eq!(map.ftp("Anna week 4"), Some(250.0));
+        assert_eq!(map.ftp("Ben #2"), Some(280.0));
+        assert_eq!(map.ftp("Chris"), Some(200.0));
+
+        // without default, the FTP of the worksheet is used
+        let map: FtpMap = "Anna * = 250".parse().unwrap();
+        assert_eq!(map.ftp("Chris"), None);
+        assert_eq!(map, FtpMap { 
+            entries: vec![("Anna *".to_string(), 250.0)], 
+            default: None 
+        });
+
+        assert!("Anna".parse::<FtpMap>().is_err());
+        assert!("Anna = -1".parse::<FtpMap>().is_err());
+    }
+
+    #[test]
+    fn smoothed_watts() {
+        assert_eq!(smooth(&[1.0, 1.0, 4.0, 1.0, 1.0], 3), 
+                   [1.0, 2.0, 2.0, 2.0, 1.0]);
+        assert_eq!(smooth(&[1.0, 1.0, 6.0, 1.0, 1.0], 5), 
+                   [1.0, 8.0 / 3.0, 2.0, 8.0 / 3.0, 1.0]);
+        assert_eq!(smooth(&[1.0, 3.0], 1), [1.0, 3.0]);
+        assert_eq!(smooth(&[], 3), Vec::<f64>::new());
+
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5\n5,0.5\n5,1.5\n6,1.5\n\
+                   6,0.5\n11,0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let rows = |options: &WriteOptions| {
+            let mut erg = Vec::new();
+            write_erg(&workout, options, &mut erg).unwrap();
+            String::from_utf8(erg).unwrap().lines()
+                .skip_while(|line| *line != "[COURSE DATA]").skip(1)
+                .take(workout.workout_data.len())
+                .map(|line| line.split('\t').nth(1).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rows(&WriteOptions::default()), 
+                   ["100", "100", "300", "300", "100", "100"]);
+        let options = WriteOptions { smooth: Some(3), ..Default::default() };
+        assert_eq!(rows(&options), ["100", "167", "233", "233", "167", "100"]);
+        // only the written watts are smoothed
+        assert_eq!(workout.workout_data[2].intensity, 1.5);
+    }
+
+    #[test]
+    fn row_limit() {
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5\n10,0.5\n10,0.6\n20,0.6\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let mut options = WriteOptions { max_rows: 4, ..Default::default() };
+        write_erg(&workout, &options, &mut Vec::new()).unwrap();
+        options.max_rows = 3;
+        let mut erg = Vec::new();
+        let e = write_erg(&workout, &options, &mut erg).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(erg.is_empty());
+        assert!(write_mrc(&workout, &options, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn erg_version() {
+        let csv = "FTP=200\nNAME=test.erg\nUNITS=METRIC\n0,0.5\n10,0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        assert!(String::from_utf8(erg).unwrap()
+                .starts_with("[COURSE HEADER]\nVERSION = 2\nUNITS = METRIC\n"));
+        let options = WriteOptions { erg_version: 1, ..Default::default() };
+        let mut erg = Vec::new();
+        write_erg(&workout, &options, &mut erg).unwrap();
+        assert!(String::from_utf8(erg).unwrap()
+                .starts_with("[COURSE HEADER]\nVERSION = 1\n"));
+    }
+
+    #[test]
+    fn description_affixes() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Sweet spot\n0,0.9\n10,0.9\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let mut options = WriteOptions { 
+            desc_prefix: "[Plan W3]".to_string(), ..Default::default() 
+        };
+        let mut erg = Vec::new();
+        write_erg(&workout, &options, &mut erg).unwrap();
+        assert!(String::from_utf8(erg).unwrap()
+                .contains("\nDESCRIPTION = [Plan W3] Sweet spot\n"));
+
+        options.desc_suffix = "(v2)".to_string();
+        assert_eq!(options.description(""), "[Plan W3] (v2)");
+        options.desc_prefix.clear();
+        assert_eq!(options.description(""), "(v2)");
+        options.desc_suffix.clear();
+        assert_eq!(options.description(""), "");
+        assert_eq!(options.description("Sweet spot"), "Sweet spot");
+    }
+
+    #[test]
+    fn profile_chart() {
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5\n10,0.5\n10,1.0\n20,1.0\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(power_profile(&workout, 4, 2), ["  ██", "████"]);
+        assert_eq!(power_profile(&workout, 2, 1), ["▄█"]);
+        // a column per sampled second at most
+        assert_eq!(power_profile(&workout, 5000, 1)[0].chars().count(), 1200);
+        assert!(power_profile(&Workout::default(), 80, 8).is_empty());
+    }
+
+    #[test]
+    fn line_endings() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Test\n0,0.5\n10,0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let erg = "[COURSE HEADER]\nVERSION = 2\nUNITS = ENGLISH\n\
+                   DESCRIPTION = Test\nFILE NAME = test.erg\nFTP = 200\n\
+                   MINUTES WATTS\n[END COURSE HEADER]\n[COURSE DATA]\n\
+                   0.00\t100\n10.00\t100\n[END COURSE DATA]\n";
+        let mut options = WriteOptions::default();
+        let mut written = Vec::new();
+        write_erg(&workout, &options, &mut written).unwrap();
+        assert_eq!(written, erg.as_bytes());
+
+        options.line_ending = "crlf".parse().unwrap();
+        let mut written = Vec::new();
+        write_erg(&workout, &options, &mut written).unwrap();
+        assert_eq!(written, erg.replace('\n', "\r\n").as_bytes());
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(parse_erg(&written, &ParseOptions::default()).unwrap()
+                   .workout_data.len(), 2);
+
+        let mut written = Vec::new();
+        write_mrc(&workout, &options, &mut written).unwrap();
+        let mrc = String::from_utf8(written).unwrap();
+        assert!(mrc.ends_with("\r\n0.00\t50.0\r\n10.00\t50.0\r\n\
+                              [END COURSE DATA]\r\n"));
+        assert!(!mrc.replace("\r\n", "").contains('\n'));
+    }
+
+    #[test]
+    fn multiline_header() {
+        let workout = Workout {
+            ftp: 200.0,
+            file_name: "Schwelle\n4×8.erg".to_string(),
+            description: "Zone 4 – 4×8 min\r\nIF = 0.9\tno\u{7}bell"
+                .to_string(),
+            workout_data: vec![
+                WorkoutData { time: 0.0, intensity: 0.9, ..Default::default() },
+                WorkoutData { time: 8.0, intensity: 0.9, ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        let erg = String::from_utf8(erg).unwrap();
+        let header: Vec<_> = erg.lines().take(7).collect();
+        assert_eq!(header, [
+            "[COURSE HEADER]", "VERSION = 2", "UNITS = ENGLISH", 
+            "DESCRIPTION = Zone 4 – 4×8 min IF = 0.9 nobell", 
+            "FILE NAME = Schwelle 4×8.erg", "FTP = 200", "MINUTES WATTS",
+        ]);
+        let parsed = parse_erg(&erg, &ParseOptions::default()).unwrap();
+        assert_eq!(parsed.description, "Zone 4 – 4×8 min IF = 0.9 nobell");
+        assert_eq!(parsed.workout_data.len(), 2);
+    }
+
+    #[test]
+    fn time_formats() {
+        let mut options = WriteOptions::default();
+        assert_eq!(format_time(5.5, &options), "5.50");
+        options.time_precision = 0;
+        assert_eq!(format_time(5.25, &options), "5");
+        options.time_precision = 4;
+        assert_eq!(format_time(1.0 / 3.0, &options), "0.3333");
+        options.time_format = TimeFormat::MinutesSeconds;
+        assert_eq!(format_time(5.5, &options), "05:30");
+        assert_eq!(format_time(89.999, &options), "90:00");
+    }
+
+    #[test]
+    fn output_paths() {
+        let outdir = Path::new("out");
+        assert_eq!(output_path(outdir, "test.erg", "erg"), 
+                   Path::new("out/test.erg"));
+        assert_eq!(output_path(outdir, "test.ERG", "erg"), 
+                   Path::new("out/test.ERG"));
+        assert_eq!(output_path(outdir, "test", "erg"), 
+                   Path::new("out/test.erg"));
+        assert_eq!(output_path(outdir, "test.erg", "zwo"), 
+                   Path::new("out/test.zwo"));
+        assert_eq!(output_path(outdir, "week 1.5", "mrc"), 
+                   Path::new("out/week 1.5.mrc"));
+
+        assert_eq!(numbered_file_name("test.erg", 2), "test-2.erg");
+        assert_eq!(numbered_file_name("test.ERG", 3), "test-3.ERG");
+        assert_eq!(numbered_file_name("test", 2), "test-2");
+        assert_eq!(numbered_file_name("week 1.5", 2), "week 1.5-2");
+    }
+
+    /// Builds a worksheet with `header_rows` rows before the data, the header 
+    /// labels (without a `MINUTES` row) followed by filler
+    fn header_sheet(header_rows: usize) -> Range {
+        let mut rows = vec![
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("DESCRIPTION"), text("Test")],
+        ];
+        rows.resize(header_rows, vec![text("NOTE")]);
+        rows.push(vec![DataType::Float(0.0), DataType::Float(0.5)]);
+        rows.push(vec![DataType::Int(10), DataType::Float(0.5)]);
+        sheet(&rows)
+    }
+
+    #[test]
+    fn header_rows() {
+        for header_rows in [3, 6] {
+            let range = header_sheet(header_rows);
+            let workout = parse_range(&range, &ParseOptions::default())
+                .unwrap();
+            assert_eq!(workout.intervals.len(), 1);
+            assert_close(workout.ftp, 200.0);
+
+            let options = ParseOptions { 
+                header_rows: Some(header_rows), 
+                ..Default::default() 
+            };
+            let workout = parse_range(&range, &options).unwrap();
+            assert_eq!(workout.intervals.len(), 1);
+        }
+
+        // the flag overrides the detection, here the data starts too late
+        let options = ParseOptions { 
+            header_rows: Some(7), 
+            ..Default::default() 
+        };
+        assert_eq!(parse_range(&header_sheet(6), &options).unwrap_err(), 
+                   ParseError::OddDataPoints { count: 1 });
+    }
+
+    #[test]
+    fn trailing_empty_rows() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+            vec![DataType::Empty, DataType::Empty],
+            vec![DataType::Empty, DataType::Empty],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.intervals.len(), 1);
+        assert!(workout.warnings.is_empty());
+    }
+
+    #[test]
+    fn interior_empty_row() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+            vec![DataType::Empty, DataType::Empty],
+            vec![DataType::Float(10.0), DataType::Float(0.7)],
+            vec![DataType::Float(20.0), DataType::Float(0.7)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.intervals.len(), 2);
+        assert_eq!(workout.warnings, 
+                   vec![Warning::EmptyRow { row: 6 }, 
+                        Warning::NoCooldown { intensity: 0.7 }]);
+
+        let options = ParseOptions { strict: true, ..Default::default() };
+        let workout = parse_range(&range, &options).unwrap();
+        assert_eq!(workout.intervals.len(), 1);
+        assert!(workout.warnings.is_empty());
+    }
+
+    #[test]
+    fn strict_warnings() {
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5\n10,0.5\n10,0.9\n10,0.9\n\
+                   10,0.5\n20,0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(workout.intervals.len(), 2);
+        assert_eq!(workout.warnings, 
+                   vec![Warning::ZeroDuration { time: 10.0 }]);
+
+        let options = ParseOptions { strict: true, ..Default::default() };
+        assert_eq!(parse_workout_csv(csv, &options).unwrap_err(), 
+                   ParseError::Strict { 
+                       warning: Warning::ZeroDuration { time: 10.0 } 
+                   });
+        let csv = "FTP=200\nNAME=test.erg\n0,85\n10,85\n";
+        assert_eq!(parse_workout_csv(csv, &options).unwrap_err(), 
+                   ParseError::Strict { 
+                       warning: Warning::HighIntensity { row: 3, 
+                                                         intensity: 85.0 } 
+                   });
+    }
+
+    #[test]
+    fn data_columns() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("NOTES"), text("WATTS")],
+            vec![DataType::Float(0.0), text("easy"), DataType::Int(100)],
+            vec![DataType::Float(10.0), DataType::Empty, DataType::Int(100)],
+            vec![DataType::Float(10.0), DataType::Float(3.0), 
+                 DataType::Int(300), text("Threshold")],
+            vec![DataType::Float(13.0), text("hard"), DataType::Int(300)],
+        ]);
+        let options = ParseOptions {
+            columns: DataColumns { time: 0, intensity: 2 },
+            ..Default::default()
+        };
+        let workout = parse_range(&range, &options).unwrap();
+        assert_eq!(workout.intervals.len(), 2);
+        assert_close(workout.intervals[0].watt, 100.0);
+        assert_close(workout.intervals[1].watt, 300.0);
+        assert_eq!(workout.intervals[1].label.as_deref(), Some("Threshold"));
+        // the notes aren't numbers
+        assert!(matches!(parse_range(&range, &ParseOptions::default()), 
+                         Err(ParseError::BadRow { row: 4, .. })));
+
+        // the header ends at the relocated data labels
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("DESCRIPTION"), text("Easy")],
+            vec![DataType::Empty, text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Empty, DataType::Float(0.0), DataType::Float(0.5)],
+            vec![text("DATE"), text("2024-03-05")],
+            vec![DataType::Empty, DataType::Float(10.0), DataType::Float(0.5)],
+        ]);
+        let columns = DataColumns { time: 1, intensity: 2 };
+        let options = ParseOptions { columns, ..Default::default() };
+        let workout = parse_range(&range, &options).unwrap();
+        assert_eq!(workout.description, "Easy");
+        assert_eq!(workout.intervals.len(), 1);
+        assert_eq!(sheet_date(&range, columns), None);
+        assert!(sheet_date(&range, DataColumns::default()).is_some());
+
+        assert_eq!(parse_column("C"), Ok(2));
+        assert_eq!(parse_column("c"), Ok(2));
+        assert_eq!(parse_column("AA"), Ok(26));
+        assert_eq!(parse_column("2"), Ok(2));
+        assert!(parse_column("").is_err());
+        assert!(parse_column("B2").is_err());
+    }
+
+    #[test]
+    fn comment_rows() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+            vec![text("START OF THRESHOLD SET")],
+            vec![DataType::Float(10.0), DataType::Float(1.0)],
+            vec![text("max effort"), text("!")],
+            vec![DataType::Float(20.0), DataType::Float(1.0)],
+            vec![text(" 20 "), DataType::Float(0.5)],
+            vec![DataType::Float(25.0), DataType::Float(0.5)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.intervals.len(), 3);
+        assert!(workout.warnings.is_empty());
+
+        // numbers stay data points, even if they are malformed
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), text("easy"), DataType::Empty],
+        ]);
+        let e = parse_range(&range, &ParseOptions::default()).unwrap_err();
+        assert_eq!(e, ParseError::BadRow { 
+            row: 5, 
+            cells: r#"[Float(10.0), String("easy")]"#.to_string() 
+        });
+        assert_eq!(e.to_string(), "row 5 is not a pair of numbers: \
+                                   [Float(10.0), String(\"easy\")]");
+    }
+
+    #[test]
+    fn repeated_blocks() {
+        let header = || vec![
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+        ];
+        let point = |time: f64, intensity: f64| 
+            vec![DataType::Float(time), DataType::Float(intensity)];
+        let mut repeated = header();
+        repeated.extend(vec![
+            vec![text("REPEAT 2x")],
+            point(10.0, 1.1), point(14.0, 1.1), point(14.0, 0.5), 
+            point(17.0, 0.5),
+            vec![text("End Repeat")],
+            point(17.0, 0.4), point(22.0, 0.4),
+        ]);
+        let mut manual = header();
+        manual.extend(vec![
+            point(10.0, 1.1), point(14.0, 1.1), point(14.0, 0.5), 
+            point(17.0, 0.5),
+            point(17.0, 1.1), point(21.0, 1.1), point(21.0, 0.5), 
+            point(24.0, 0.5),
+            point(24.0, 0.4), point(29.0, 0.4),
+        ]);
+        let options = ParseOptions::default();
+        let points = |rows: &[Vec<DataType>]| {
+            let workout = parse_range(&sheet(rows), &options).unwrap();
+            assert!(workout.markers.is_empty());
+            (workout.workout_data.iter()
+                 .map(|d| (d.time, d.intensity))
+                 .collect::<Vec<_>>(), 
+             workout.tss)
+        };
+        assert_eq!(points(&repeated), points(&manual));
+
+        // the last block doesn't need an END REPEAT
+        let mut open = header();
+        open.extend(vec![vec![text("repeat 3")], point(10.0, 1.0), 
+                         point(12.0, 1.0)]);
+        assert_eq!(points(&open).0.last(), Some(&(16.0, 1.0)));
+
+        // a block with half an interval
+        let mut odd = header();
+        odd.extend(vec![point(10.0, 1.0), vec![text("REPEAT 2")], 
+                        point(12.0, 1.0), vec![text("END REPEAT")]]);
+        assert_eq!(parse_range(&sheet(&odd), &options).unwrap_err(), 
+                   ParseError::BadRepeat { row: 7 });
+    }
+
+    #[test]
+    fn dates() {
+        let date = |year, month, day| Date::new(year, month, day).unwrap();
+        assert_eq!(find_date("2024-03-05 threshold"), Some(date(2024, 3, 5)));
+        assert_eq!(find_date("week_2024_12_31"), Some(date(2024, 12, 31)));
+        assert_eq!(find_date("ss 20240229"), Some(date(2024, 2, 29)));
+        assert_eq!(find_date("ss 20230229"), None);
+        assert_eq!(find_date("build_1_2_vo2max"), None);
+        assert_eq!(find_date("202403051"), None);
+        assert_eq!(find_date("2024-03_05"), None);
+        assert_eq!("2024-03-05".parse(), Ok(date(2024, 3, 5)));
+        assert!("2024-3-5".parse::<Date>().is_err());
+        assert!(date(2024, 3, 5) < date(2024, 10, 1));
+
+        assert_eq!(Date::from_days(0), date(1970, 1, 1));
+        assert_eq!(Date::from_days(19787), date(2024, 3, 5));
+        let columns = DataColumns::default();
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200), text("DATE"), 
+                 DataType::Float(45356.75)],
+            vec![text("MINUTES"), text("INTENSITY")],
+        ]);
+        assert_eq!(sheet_date(&range, columns), Some(date(2024, 3, 5)));
+        let range = sheet(&[vec![text("Date"), text("next week")]]);
+        assert_eq!(sheet_date(&range, columns), None);
+    }
+
+    #[test]
+    fn ftp_config() {
+        assert_eq!(parse_ftp_config("285\n"), Ok(285.0));
+        let config = "# rider\nname = \"me\"\nftp = 290 # May\n";
+        assert_eq!(parse_ftp_config(config), Ok(290.0));
+        assert_eq!(parse_ftp_config("FTP=301.5"), Ok(301.5));
+        assert!(parse_ftp_config("name = \"me\"\n").is_err());
+        assert!(parse_ftp_config("ftp = 0").is_err());
+        assert!(parse_ftp_config("ftp = high").is_err());
+    }
+
+    #[test]
+    fn cadence_column() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("MINUTES"), text("INTENSITY"), text("CADENCE")],
+            vec![DataType::Float(0.0), DataType::Float(0.5), DataType::Empty],
+            vec![DataType::Float(10.0), DataType::Float(0.5), DataType::Empty],
+            vec![DataType::Float(10.0), DataType::Float(0.8), 
+                 DataType::Int(60)],
+            vec![DataType::Float(20.0), DataType::Float(0.8), 
+                 DataType::Int(60)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.intervals[0].cadence, None);
+        assert_eq!(workout.intervals[1].cadence, Some(60.0));
+
+        let mut zwo = Vec::new();
+        write_zwo(&workout, &mut zwo).unwrap();
+        assert!(String::from_utf8(zwo).unwrap().contains(
+            "<SteadyState Duration=\"600\" Power=\"0.8\" Cadence=\"60\"/>"));
+
+        let csv = "NAME=test.erg\nFTP=200\n0,0.5,90\n10,0.5,90\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(workout.workout_data[1].cadence, Some(90.0));
+    }
+
+    #[test]
+    fn detailed_summary() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Test\n\
+                   0,0.5\n10,0.5\n10,1.0\n15,1.0\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_close(workout.mean_interval_duration(), 7.5);
+        assert!(!format!("{}", workout).contains("intervals"));
+        assert!(format!("{:#}", workout)
+                .ends_with("|   2 intervals of   7.5 min | Test"));
+        assert_close(Workout::default().mean_interval_duration(), 0.0);
+    }
+
+    #[test]
+    fn multi_cell_description() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("test.erg")],
+            vec![text("DESCRIPTION"), text("4x8 min sweet spot")],
+            vec![DataType::Empty, text(" with\n5 min breaks")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        assert_eq!(workout.description, 
+                   "4x8 min sweet spot with\n5 min breaks");
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        assert!(String::from_utf8(erg).unwrap().contains(
+            "\nDESCRIPTION = 4x8 min sweet spot with 5 min breaks\n"));
+    }
+
+    #[test]
+    fn summary_formats() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Easy | short\n\
+                   0,0.5\n10,0.5\n10,1.0\n15,1.0\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let line = |format: SummaryFormat, detailed| 
+            format.join(&summary_cells(&workout, format, detailed, 0));
+
+        assert_eq!(line(SummaryFormat::Plain, false), 
+                   "test.erg                 | TSS:    12 | TSS/h:  50 | \
+                    NP:  155 | IF: 0.78 | Easy | short");
+        assert_eq!(line(SummaryFormat::Plain, false), workout.to_string());
+        assert_eq!(line(SummaryFormat::Plain, true), format!("{:#}", workout));
+        assert_eq!(SummaryFormat::Plain.header(&summary_labels(false)), None);
+
+        assert_eq!(line(SummaryFormat::Tsv, true), 
+                   "test.erg\t12\t50\t155\t0.78\t2\t7.5\tEasy | short");
+        assert_eq!(SummaryFormat::Tsv.header(&summary_labels(false)).unwrap(), 
+                   "FILE NAME\tTSS\tTSS/H\tNP\tIF\tDESCRIPTION");
+
+        assert_eq!(line(SummaryFormat::Markdown, false), 
+                   "| test.erg | 12 | 50 | 155 | 0.78 | Easy \\| short |");
+        assert_eq!(SummaryFormat::Markdown.header(&["A", "B"]).unwrap(), 
+                   "| A | B |\n| --- | --- |");
+
+        let total = |format| total_summary(100.7, 2, 90.0, format, 0);
+        assert_eq!(total(SummaryFormat::Plain), 
+                   "TOTAL WEEK TSS: 101 | 2 workout(s) | 1.5 h");
+        assert_eq!(total(SummaryFormat::Tsv), "TOTAL WEEK TSS\t101\t2\t1.5");
+        assert_eq!(total(SummaryFormat::Markdown), 
+                   "\n**TOTAL WEEK TSS: 101** (2 workout(s), 1.5 h)");
+        assert_eq!(total_summary(100.74, 2, 90.0, SummaryFormat::Plain, 1), 
+                   "TOTAL WEEK TSS: 100.7 | 2 workout(s) | 1.5 h");
+    }
+
+    #[test]
+    fn tss_comparison() {
+        let sheets = |sheets: &[(&str, f64)]| sheets.iter()
+            .map(|(sheet, tss)| (sheet.to_string(), *tss))
+            .collect::<Vec<_>>();
+        let old = sheets(&[("week1", 50.2), ("week2", 80.0), ("rest", 10.0)]);
+        let new = sheets(&[("week2", 84.6), ("week1", 49.8), ("week3", 90.0)]);
+        let changes = compare_tss(&old, &new);
+        let changed: Vec<_> = changes.iter()
+            .map(|change| (change.sheet.as_str(), change.changed(0)))
+            .collect();
+        assert_eq!(changed, [("week2", true), ("week1", false), 
+                             ("week3", true), ("rest", true)]);
+        assert!(changes[1].changed(1));
+        assert_eq!(comparison_table(&changes, "old.xlsx", "new.xlsx", 0), [
+            "WORKSHEET                 OLD TSS  NEW TSS    DELTA",
+            "week2                          80       85       +5",
+            "week1                          50       50        0",
+            "week3                           -       90        - only in \
+             new.xlsx",
+            "rest                           10        -        - only in \
+             old.xlsx",
+        ]);
+    }
+
+    #[test]
+    fn rounded_summary() {
+        let csv = "FTP=200\nNAME=test.erg\n0,1.0\n60,1.0\n";
+        let mut workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        // borderline values aren't truncated
+        workout.tss = 99.8;
+        let cells = summary_cells(&workout, SummaryFormat::Tsv, false, 0);
+        assert_eq!(cells[1], "100");
+        assert!(workout.to_string().contains("| TSS:   100 |"));
+        let cells = summary_cells(&workout, SummaryFormat::Tsv, false, 1);
+        assert_eq!(cells[1], "99.8");
+    }
+
+    #[test]
+    fn tss_per_hour() {
+        // half an hour at 80% FTP
+        let csv = "FTP=200\nNAME=test.erg\n0,0.8\n30,0.8\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_close(workout.tss, 32.0);
+        assert_close(workout.tss_per_hour, 64.0);
+        assert!(format!("{}", workout).contains("| TSS/h:  64 |"));
+        assert_close(Workout::default().density(), 0.0);
+    }
+
+    #[test]
+    fn time_in_seconds() {
+        let header = "FTP=200\nNAME=test.erg\nDESC=Tempo\n";
+        let minutes = format!("{}0,0.5\n5,0.5\n5,0.8\n20.5,0.8\n", header);
+        let seconds = format!("{}0,0.5\n300,0.5\n300,0.8\n1230,0.8\n", 
+                              header);
+        let options = ParseOptions { 
+            time_unit: TimeUnit::Seconds, ..Default::default() 
+        };
+        let expected = parse_workout_csv(&minutes, &ParseOptions::default())
+            .unwrap();
+        let workout = parse_workout_csv(&seconds, &options).unwrap();
+        assert_eq!(workout.duration(), 20.5);
+        assert_eq!(workout.tss, expected.tss);
+
+        let write = |workout: &Workout| {
+            let mut erg = Vec::new();
+            write_erg(workout, &WriteOptions::default(), &mut erg).unwrap();
+            String::from_utf8(erg).unwrap()
+        };
+        assert_eq!(write(&workout), write(&expected));
+    }
+
+    #[test]
+    fn csv_workout() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Test\n\n0,0.5\n10, 0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default()).unwrap();
+        assert_close(workout.ftp, 200.0);
+        assert_eq!(workout.file_name, "test.erg");
+        assert_eq!(workout.description, "Test");
+        assert_eq!(workout.intervals.len(), 1);
+        assert_close(workout.intervals[0].watt, 100.0);
+
+        assert_eq!(parse_workout_csv("NAME=test.erg\n0,0.5\n10\n", 
+                                     &ParseOptions::default())
+                   .unwrap_err(), 
+                   ParseError::BadRow { row: 3, cells: r#""10""#.into() });
+    }
+
+    #[test]
+    fn missing_cooldown() {
+        let options = ParseOptions::default();
+        let csv = "FTP=200\nNAME=test.erg\n0,0.9\n10,0.9\n10,0.5\n15,0.5\n";
+        let workout = parse_workout_csv(csv, &options).unwrap();
+        assert!(workout.warnings.is_empty());
+
+        let csv = "FTP=200\nNAME=test.erg\n0,0.5\n10,0.5\n10,0.9\n15,0.9\n";
+        let workout = parse_workout_csv(csv, &options).unwrap();
+        assert_eq!(workout.warnings, 
+                   vec![Warning::NoCooldown { intensity: 0.9 }]);
+    }
+
+    #[test]
+    fn erg_round_trip() {
+        let csv = "FTP=250\nNAME=test.erg\nDESC=Ramps\n\
+                   0,0.5\n10,0.8\n10,1.05\n14,1.05\n14,0.5\n20,0.4\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        for time_format in [TimeFormat::Minutes, TimeFormat::MinutesSeconds] {
+            for resolution in [None, Some(0.5)] {
+                let mut written = 
+                    parse_workout_csv(csv, &ParseOptions::default()).unwrap();
+                if let Some(resolution) = resolution {
+                    written.interpolate_ramps(resolution);
+                }
+                let options = WriteOptions { 
+                    time_format, 
+                    ..Default::default() 
+                };
+                let mut erg = Vec::new();
+                write_erg(&written, &options, &mut erg).unwrap();
+                let parsed = parse_erg(&String::from_utf8(erg).unwrap(), 
+                                       &ParseOptions::default()).unwrap();
+                assert_eq!(parsed.file_name, "test.erg");
+                assert_eq!(parsed.description, "Ramps");
+                assert_close(parsed.ftp, 250.0);
+                assert!((parsed.tss - workout.tss).abs() 
+                        < workout.tss * VERIFY_TOLERANCE);
+            }
+        }
+
+        // the parsed workout is the written one and writes the same file
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        let erg = String::from_utf8(erg).unwrap();
+        let options = ParseOptions::default();
+        let parsed = parse_erg(&erg, &options).unwrap();
+        let points = |workout: &Workout| workout.workout_data.iter()
+            .map(|d| (d.time, (d.intensity * 100.0).round()))
+            .collect::<Vec<_>>();
+        assert_eq!(points(&parsed), points(&workout));
+        assert_eq!(parsed.intervals.len(), workout.intervals.len());
+        let mut rewritten = Vec::new();
+        write_erg(&parsed, &WriteOptions::default(), &mut rewritten).unwrap();
+        assert_eq!(String::from_utf8(rewritten).unwrap(), erg);
+
+        // comments and other sections are skipped, the FTP can be replaced
+        let erg = "[COURSE HEADER]\nexported by hand\n; FTP = 100\nFTP = 200\n\
+                   FILE NAME = a.erg\nMINUTES WATTS\n[END COURSE HEADER]\n\
+                   [COURSE DATA]\n0\t100\n10\t100\n[END COURSE DATA]\n\
+                   [COURSE TEXT]\n0\tWarm up\t5\n[END COURSE TEXT]\n";
+        let parsed = parse_erg(erg, &options).unwrap();
+        assert_eq!(parsed.ftp, 200.0);
+        assert_close(parsed.tss, 10.0 / 60.0 * 25.0);
+        let ftp = ParseOptions { ftp: Some(100.0), ..Default::default() };
+        assert_close(parse_erg(erg, &ftp).unwrap().tss, 10.0 / 60.0 * 100.0);
+
+        assert_eq!(parse_erg("FILE NAME = a\n[COURSE DATA]\n0\t100\n", &options)
+                   .unwrap_err(), ParseError::MissingFtp);
+        assert_eq!(parse_erg("FTP = 200\nFILE NAME = a\n[COURSE DATA]\n0\n", 
+                             &options).unwrap_err(), 
+                   ParseError::BadRow { row: 4, cells: r#""0""#.into() });
+    }
+
+    #[test]
+    fn import_erg() {
+        let csv = "FTP=200\nNAME=test.erg\nDESC=Imported\n\
+                   0,0.5\n10,0.8\n10,1.1\n14,1.1\n14,0.6\n20,0.4\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let mut erg = Vec::new();
+        write_erg(&workout, &WriteOptions::default(), &mut erg).unwrap();
+        let imported = parse_erg(&String::from_utf8(erg).unwrap(), 
+                                 &ParseOptions::default()).unwrap();
+
+        // the erg file carries the same intervals and TSS as the worksheet
+        assert_eq!(imported.file_name, workout.file_name);
+        assert_eq!(imported.description, workout.description);
+        assert_eq!(imported.ftp, workout.ftp);
+        assert_eq!(imported.intervals.len(), workout.intervals.len());
+        for (imported, original) in imported.intervals.iter()
+            .zip(&workout.intervals) {
+            assert_close(imported.duration, original.duration);
+            assert_close(imported.start_intensity, original.start_intensity);
+            assert_close(imported.end_intensity, original.end_intensity);
+            assert_close(imported.tss, original.tss);
+        }
+        assert_close(imported.tss, workout.tss);
+        assert_close(imported.normalized_power, workout.normalized_power);
+    }
+
+    #[test]
+    fn resampled_workout() {
+        let csv = "FTP=200\nNAME=test.erg\n\
+                   0,0.5\n1.25,0.5\n1.25,0.5\n2.25,1.0\n2.25,0.6\n3,0.6\n";
+        let options = ParseOptions::default();
+        let boundaries = parse_workout_csv(csv, &options).unwrap();
+        let mut resampled = parse_workout_csv(csv, &options).unwrap();
+        resampled.resample(0.5);
+        let points: Vec<_> = resampled.workout_data.iter()
+            .map(|d| (d.time, d.intensity))
+            .collect();
+        assert_eq!(points, [
+            (0.0, 0.5), (0.5, 0.5), (1.0, 0.5), (1.25, 0.5), 
+            (1.25, 0.5), (1.5, 0.625), (2.0, 0.875), (2.25, 1.0), 
+            (2.25, 0.6), (2.5, 0.6), (3.0, 0.6),
+        ]);
+        assert_eq!(resampled.intervals.len(), boundaries.intervals.len());
+        assert_close(resampled.tss, boundaries.tss);
+        assert_close(resampled.normalized_power, boundaries.normalized_power);
+
+        // the written rows are evenly spaced, apart from the boundaries
+        let rows = |workout: &Workout| {
+            let mut erg = Vec::new();
+            write_erg(workout, &WriteOptions::default(), &mut erg).unwrap();
+            String::from_utf8(erg).unwrap().lines()
+                .filter(|line| line.contains('\t'))
+                .count()
+        };
+        assert_eq!(rows(&boundaries), 6);
+        assert_eq!(rows(&resampled), 11);
+    }
+
+    #[test]
+    fn interval_table() {
+        let csv = "FTP=200\nNAME=test.erg\n\
+                   0,0.5\n10,0.5\n10,0.5\n12.5,1.0\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let mut table = Vec::new();
+        write_intervals(&workout, &WriteOptions::default(), &mut table)
+            .unwrap();
+        let expected = [
+            "  # |   START |     END |  MINUTES | WATTS |   IF |   TSS",
+            "  1 |    0.00 |   10.00 |    10.00 |   100 | 0.50 |   4.2",
+            "  2 |   10.00 |   12.50 |     2.50 |   150 | 0.76 |   2.4",
+        ];
+        assert_eq!(String::from_utf8(table).unwrap(), 
+                   expected.join("\n") + "\n");
+        assert_eq!(output_path(Path::new("out"), "test.erg", "intervals.txt"), 
+                   Path::new("out/test.intervals.txt"));
+    }
+
+    #[test]
+    fn debug_intervals() {
+        let csv = "FTP=200\nNAME=test.erg\n\
+                   0,0.5\n10,0.5\n10,1.0\n12.5,1.0\n12.5,0.5\n20,0.5\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        let expected = [
+            "  #     START       END  DURATION     TSS   TOTAL",
+            "  1     0.000    10.000    10.000    4.17    4.17",
+            "  2    10.000    12.500     2.500    4.17    8.33",
+            "  3    12.500    20.000     7.500    3.12   11.46",
+        ];
+        assert_eq!(interval_dump(&workout), expected);
+    }
+
+    #[test]
+    fn defined_names() {
+        let workbook = r#"<workbook><sheets>
+            <sheet name="Week 1" sheetId="1" r:id="rId1"/>
+            <sheet name="Tom's" sheetId="2" r:id="rId2"/>
+            </sheets><definedNames>
+            <definedName name="Meta">'Week 1'!$E$1:$F$3</definedName>
+            <definedName name="Meta" localSheetId="1">'Tom''s'!$H$2:$I$4
+            </definedName>
+            <definedName name="Data">'Week 1'!$A$1:$B$4</definedName>
+            <definedName name="Rate">0.9</definedName>
+            <definedName name="Print">'Week 1'!$A$1,'Week 1'!$E$1
+            </definedName>
+            </definedNames></workbook>"#;
+        let names = parse_defined_names(workbook);
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[1], DefinedName { 
+            name: "Meta".to_string(), 
+            scope: Some("Tom's".to_string()), 
+            sheet: "Tom's".to_string(), 
+            area: "H2:I4".parse().unwrap(),
+        });
+        assert_eq!(named_area(&names, "Tom's", "meta"), Some(CellArea { 
+            first: (1, 7), 
+            last: (3, 8),
+        }));
+        assert_eq!(named_area(&names, "Week 1", "Meta"), 
+                   Some("E1:F3".parse().unwrap()));
+        assert_eq!(named_area(&names, "Week 2", "Meta"), None);
+        assert!("A0".parse::<CellArea>().is_err());
+        assert!("$A:$B".parse::<CellArea>().is_err());
+
+        // header right of the data points
+        let mut range = Range::new((1, 1), (5, 6));
+        let rows = [
+            vec![text("MINUTES"), text("INTENSITY"), DataType::Empty, 
+                 DataType::Empty, text("FTP"), DataType::Float(200.0)],
+            vec![DataType::Float(0.0), DataType::Float(0.5), DataType::Empty, 
+                 DataType::Empty, text("FILE NAME"), text("named.erg")],
+            vec![DataType::Float(10.0), DataType::Float(0.5), DataType::Empty, 
+                 DataType::Empty, text("DESCRIPTION"), text("Named")],
+            vec![DataType::Float(10.0), DataType::Float(1.0)],
+            vec![DataType::Float(15.0), DataType::Float(1.0)],
+        ];
+        for (i, row) in rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                range.set_value((i as u32 + 1, j as u32 + 1), value.clone());
+            }
+        }
+        let parse = |metadata: Option<&str>, data: Option<&str>| {
+            let named = named_range(&range, 
+                                    metadata.map(|m| m.parse().unwrap()), 
+                                    data.map(|d| d.parse().unwrap()));
+            parse_range(&named, &ParseOptions::default())
+        };
+        for (metadata, data) in [(Some("E1:F3"), Some("A1:B5")), 
+                                 (Some("E1:F3"), None), 
+                                 (None, Some("A1:B5"))] {
+            let workout = parse(metadata, data).unwrap();
+            assert_eq!(workout.ftp, 200.0);
+            assert_eq!(workout.file_name, "named.erg");
+            assert_eq!(workout.description, "Named");
+            assert_eq!(workout.workout_data.len(), 4);
+        }
+        // without the MINUTES row, the data starts at the first numbers
+        let workout = parse(Some("E1:F3"), Some("A2:B5")).unwrap();
+        assert_eq!(workout.workout_data.len(), 4);
+    }
+
+    #[test]
+    fn validated_sheet() {
+        let range = sheet(&[
+            vec![text("FTP"), text("high")],
+            vec![text("DESCRIPTION"), DataType::Float(1.0)],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), text("easy")],
+            vec![DataType::Float(12.0), DataType::Float(0.5)],
+            vec![DataType::Float(5.0), DataType::Float(0.9)],
+            vec![],
+            vec![DataType::Float(20.0), DataType::Float(0.9)],
+        ]);
+        let options = ParseOptions::default();
+        assert_eq!(validate_range(&range, &options), [
+            ParseError::MissingFileName, 
+            ParseError::MissingDescription, 
+            ParseError::MissingFtp,
+            ParseError::BadRow { 
+                row: 5, 
+                cells: "[Float(10.0), String(\"easy\")]".to_string() 
+            },
+            ParseError::TimeNotIncreasing { row: 7 },
+            ParseError::OddDataPoints { count: 3 },
+        ]);
+        // parsing stops at the first one
+        assert_eq!(parse_range(&range, &options).unwrap_err(), 
+                   ParseError::MissingFileName);
+
+        let valid = |rows: &[Vec<DataType>]| {
+            let mut cells = vec![
+                vec![text("FTP"), DataType::Int(200)],
+                vec![text("FILE NAME"), text("test.erg")],
+                vec![text("MINUTES"), text("INTENSITY")],
+            ];
+            cells.extend_from_slice(rows);
+            sheet(&cells)
+        };
+        let range = valid(&[vec![DataType::Float(0.0), DataType::Float(0.5)], 
+                            vec![DataType::Float(10.0), DataType::Float(0.5)]]);
+        assert!(validate_range(&range, &options).is_empty());
+        let range = valid(&[vec![DataType::Float(0.0), DataType::Float(1.0)], 
+                            vec![], 
+                            vec![DataType::Float(10.0), DataType::Float(1.0)]]);
+        // warnings fail as well, even when parsing ends at empty rows
+        let strict = ParseOptions { strict: true, ..Default::default() };
+        assert_eq!(validate_range(&range, &strict), [
+            ParseError::Strict { warning: Warning::EmptyRow { row: 5 } }, 
+            ParseError::Strict { 
+                warning: Warning::NoCooldown { intensity: 1.0 } 
+            },
+        ]);
+    }
+
+    #[test]
+    fn file_name_templates() {
+        let csv = "FTP=200\nNAME=vo2max.erg\n0,1.0\n30,1.0\n";
+        let workout = parse_workout_csv(csv, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(render_file_name("{date}_{sheet}_{tss}.erg", &workout, 
+                                    "build_1_2", "2024-03-01").unwrap(), 
+                   "2024-03-01_build_1_2_50.erg");
+        assert_eq!(render_file_name("{name} {minutes}min", &workout, "", "")
+                   .unwrap(), "vo2max 30min");
+        assert_eq!(render_file_name("a/b: {sheet}", &workout, "x?", "")
+                   .unwrap(), "a_b_ x_");
+        assert!(render_file_name("{foo}", &workout, "", "").is_err());
+        assert!(render_file_name("{sheet", &workout, "", "").is_err());
+    }
+
+    #[test]
+    fn json_report() {
+        let workout = parse_workout_csv(
+            "FTP=200\nNAME=a.erg\n0,1.0\n30,1.0\n", &ParseOptions::default())
+            .unwrap();
+        let report = Report {
+            success: false,
+            error: Some("1 worksheet(s) couldn't be read".to_string()),
+            total_tss: workout.tss,
+            merged: None,
+            sheets: vec![
+                SheetReport::converted("plan.xlsx", Some("a"), &workout, 
+                                       Some("out/a.erg")),
+                SheetReport::failed("plan.xlsx", Some("b"), 
+                                    &ParseError::MissingFtp),
+            ],
+        };
+        let mut json = Vec::new();
+        write_report(&report, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["total_tss"], 50.0);
+        assert_eq!(json["sheets"][0]["output"], "out/a.erg");
+        assert_eq!(json["sheets"][0]["tss"], 50.0);
+        assert_eq!(json["sheets"][1]["converted"], false);
+        assert_eq!(json["sheets"][1]["error"], 
+                   ParseError::MissingFtp.to_string());
+        assert!(json["sheets"][1]["tss"].is_null());
+    }
+
+    #[test]
+    fn merged_workouts() {
+        let options = ParseOptions::default();
+        let warmup = parse_workout_csv(
+            "FTP=200\nNAME=a.erg\nDESC=A\n0,0.5\n10,0.5\n", &options)
+            .unwrap();
+        let main = parse_workout_csv(
+            "FTP=200\nNAME=b.erg\nDESC=B\n5,1.0\n25,1.0\n", &options)
+            .unwrap();
+        let merged = merge_workouts(&[warmup, main], "merged.erg").unwrap();
+        assert_eq!(merged.description, "A + B");
+        let times: Vec<f64> = merged.workout_data.iter()
+            .map(|d| d.time)
+            .collect();
+        assert_eq!(times, [0.0, 10.0, 10.0, 30.0]);
+        assert_eq!(merged.intervals.len(), 2);
+        let labels: Vec<_> = merged.intervals.iter()
+            .map(|interval| interval.label.as_deref())
+            .collect();
+        assert_eq!(labels, [Some("A"), Some("B")]);
+        // 10 min at 50% and 20 min at 100%
+        assert_close(merged.tss, 10.0 / 60.0 * 25.0 + 20.0 / 60.0 * 100.0);
+
+        // all workouts with a different FTP are named
+        let workout = |ftp: f64, name: &str| parse_workout_csv(
+            &format!("FTP={}\nNAME={}\nDESC=C\n0,0.5\n10,0.5\n", ftp, name), 
+            &options).unwrap();
+        let workouts = [workout(200.0, "a.erg"), workout(250.0, "b.erg"), 
+                        workout(200.0, "c.erg"), workout(260.0, "d.erg")];
+        let error = merge_workouts(&workouts, "merged.erg").unwrap_err();
+        assert_eq!(error, MergeError::FtpMismatch { 
+            expected: 200.0, 
+            mismatches: vec![("b.erg".to_string(), 250.0), 
+                             ("d.erg".to_string(), 260.0)],
+        });
+        assert_eq!(error.to_string(), "FTP differs from the 200 of the first \
+                                       workout: b.erg (250), d.erg (260)");
+        // rounding errors are tolerated
+        let workouts = [workout(200.0, "a.erg"), 
+                        workout(200.0 + FTP_TOLERANCE / 2.0, "b.erg")];
+        assert!(merge_workouts(&workouts, "merged.erg").is_ok());
+        // a forced FTP keeps the intensities
+        let mut workouts = [workout(200.0, "a.erg"), workout(250.0, "b.erg")];
+        for workout in &mut workouts {
+            workout.set_ftp(300.0);
+        }
+        let merged = merge_workouts(&workouts, "merged.erg").unwrap();
+        assert_eq!(merged.ftp, 300.0);
+        assert_eq!(merged.workout_data[2].intensity, 0.5);
+        assert_close(merged.tss, 2.0 * 10.0 / 60.0 * 25.0);
+        assert_eq!(merge_workouts(&[], "merged.erg").unwrap_err(), 
+                   MergeError::NoWorkouts);
+    }
+
+    #[test]
+    fn split_segments() {
+        let range = sheet(&[
+            vec![text("FTP"), DataType::Int(200)],
+            vec![text("FILE NAME"), text("ride.erg")],
+            vec![text("DESCRIPTION"), text("Long ride")],
+            vec![text("MINUTES"), text("INTENSITY")],
+            vec![text("Warmup")],
+            vec![DataType::Float(0.0), DataType::Float(0.5)],
+            vec![DataType::Float(10.0), DataType::Float(0.5)],
+            vec![text("Tempo")],
+            vec![DataType::Float(10.0), DataType::Float(0.8)],
+            vec![text("halfway")],
+            vec![DataType::Float(40.0), DataType::Float(0.8)],
+            vec![DataType::Float(40.0), DataType::Float(0.6)],
+            vec![DataType::Float(70.0), DataType::Float(0.6)],
+        ]);
+        let workout = parse_range(&range, &ParseOptions::default()).unwrap();
+        let segments = split_workout(&workout);
+        assert_eq!(segments.len(), 2);
+        let (warmup, tempo) = (&segments[0], &segments[1]);
+        assert_eq!(warmup.file_name, "ride-1.erg");
+        assert_eq!(warmup.description, "Long ride - Warmup");
+        assert_eq!(tempo.file_name, "ride-2.erg");
+        assert_eq!(tempo.description, "Long ride - Tempo");
+        // the marker within the interval doesn't split it
+        let times: Vec<_> = tempo.workout_data.iter().map(|d| d.time)
+            .collect();
+        assert_eq!(times, [0.0, 30.0, 30.0, 60.0]);
+        assert_close(warmup.tss, 10.0 / 60.0 * 25.0);
+        assert_close(tempo.tss, 0.5 * 64.0 + 0.5 * 36.0);
+        assert_close(warmup.tss + tempo.tss, workout.tss);
+
+        // without markers between the intervals, there's a single segment
+        let workout = Workout { markers: Vec::new(), ..workout };
+        let segments = split_workout(&workout);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].workout_data.len(), 6);
+    }
+}
@@ -1,170 +1,1616 @@
+use std::collections::{BTreeMap, HashSet};
 use std::env;
-use std::fmt;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
-use office::{Excel, DataType};
-
-
-/// `WorkoutData` represents a single row in the xlsx worksheet.
-#[derive(Default, Debug)]
-struct WorkoutData {
-    /// Timestamp in minutes of the data point
-    time: f64,
-    /// Relative intensity at `time` in percent of FTP
-    intensity: f64,
-}
-
-/// `Interval` represents an interval which is created in the 
-/// `erg` file
-#[derive(Default, Debug)]
-struct Interval {
-    /// Time in minutes the interval takes
-    duration: f64,
-    /// Average watts of the interval
-    watt: f64,
-    /// Approximation of the intensity factor, not 100% accurate when the 
-    /// interval ramps up or down, but close enough
-    intensity_factor: f64,
-    /// Training Stress Score of the interval
-    tss: f64,
-}
-
-impl Interval {
-    /// Creates a new `Interval`, requires to consecutive `WorkoutData` points 
-    /// and the current `FTP` as parameters.
-    fn new(wd1: &WorkoutData, wd2: &WorkoutData, ftp: f64) -> Self {
-        let duration = wd2.time - wd1.time;
-        let watt = (wd1.intensity + wd2.intensity) / 2.0 * ftp;
-        let intensity_factor = watt / ftp;
-        let tss = (duration/60.0) * intensity_factor.powf(2.0) * 100.0;
-        Self {
-            duration,
-            watt,
-            intensity_factor,
-            tss,
-        }
-    }
-}
-
-/// The `Workout` struct represents the complete workout and contains 
-/// the current `FTP`, `file_name`, the `description` of the workout, 
-/// `Vectors` of `WorkoutData` and `Interval`s, as well as the total `TSS`.
-#[derive(Default, Debug)]
-struct Workout {
-    ftp: f64,
-    file_name: String,
-    description: String,
-    workout_data: Vec<WorkoutData>,
-    intervals: Vec<Interval>,
-    tss: f64,
-}
-
-impl fmt::Display for Workout {
-    /// Custom formatting so that it a quick summary of the workout can be 
-    /// printed to console after it is converted
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, r"{:24} | TSS: {:5} | {}", 
-               self.file_name, self.tss as u64, self.description)
-    }
-}
-
-/// Writes the parsed `Workout` to an `erg` file.
-fn write_erg_file(workout: Workout) {
-        let path = Path::new(&workout.file_name);
-        let mut file = File::create(&path).expect("Couldn't open file");
-        let mut file_content = format!("[COURSE HEADER]
-VERSION = 2
-UNITS = ENGLISH
-DESCRIPTION = {}
-FILE NAME = {}
-FTP = {}
-MINUTES WATTS
-[END COURSE HEADER]
-[COURSE DATA]
-", workout.description, workout.file_name, workout.ftp);
-
-        for data in workout.workout_data {
-            file_content.push_str(&format!("{:.2}\t{}\n", 
-                data.time, (data.intensity * workout.ftp) as u64));
-        }
-
-        file_content.push_str("[END COURSE DATA]\n");
-        file.write_all(file_content.as_bytes()).expect("Couldn't write file");
-}
-
-/// Parses the workbook and iterates over each worksheet. All worksheets 
-/// are then converted to `erg` files except the `Overview` worksheet.
-fn parse_workout(workbook: &mut Excel, worksheet: &str) -> Workout {
-
-    let mut workout = Workout{.. Default::default()};
-
-    if let Ok(range) = workbook.worksheet_range(worksheet) {
-        let rows = range.rows();
-        if let DataType::Float(ftp) = range.get_value(0, 1) {
-            workout.ftp = *ftp;
-        }
-        if let DataType::String(file_name) = range.get_value(1, 1) {
-            workout.file_name = file_name.to_string();
-        }
-        if let DataType::String(description) = range.get_value(2, 1) {
-            workout.description = description.to_string();
-        }
-
-        for row in rows.skip(4) {
-            match row {
-                [DataType::Float(time), DataType::Float(intensity)] => {
-                    workout.workout_data.push(
-                        WorkoutData {
-                            time: *time,
-                            intensity: *intensity,
-                        }
-                    );
-                },
-                [DataType::Empty, DataType::Empty] => {
-                    println!("EMPTY");
-                    break;
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::{Mutex, Once};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{Level, LevelFilter, Log, Metadata, Record, debug, error, info, warn};
+use rayon::prelude::*;
+use xlsx2erg::{
+    Date, DefinedName, ERG_VERSIONS, Excel, Format, FTP_TOLERANCE, FtpMap, 
+    glob_match, ParseError, ParseOptions, Range, Report, SheetOrder, 
+    SheetReport, SummaryFormat, TSS_SCALE, Units, VERIFY_TOLERANCE, Warning, 
+    Workout, WriteOptions, compare_tss, comparison_table, find_date, 
+    interval_dump, label_matches, merge_workouts, named_area, named_range, 
+    numbered_file_name, output_path, parse_column, parse_erg, parse_ftp_config, 
+    parse_range, parse_workout_csv, power_profile, read_defined_names, 
+    render_file_name, sheet_date, split_workout, validate_range, summary_cells, 
+    summary_labels, total_summary, write_erg, write_erg_file, write_fit, 
+    write_fit_file, write_gc_json, write_gc_json_file, write_hr_erg, 
+    write_hr_erg_file, write_intervals_file, write_json, write_json_file, 
+    write_mrc, write_mrc_file, write_overview_file, write_report_file, 
+    write_zwo, write_zwo_file,
+};
+
+const USAGE: &str = "Usage: xlsx2erg [options] <xlsx, xls, csv or erg file>...
+
+Options:
+    -h, --help          Print this help
+    -q, --quiet         Only print errors, e.g. in scripts
+    -v, --verbose       Print the zones and intervals of the workouts, -vv 
+                        prints every data point as well
+    --annotate          Add the total TSS and duration to the erg header
+    --desc-prefix <text>
+                        Put <text> in front of every description
+    --desc-suffix <text>
+                        Append <text> to every description
+    --detailed          Add the number and average duration of the intervals 
+                        to the summaries
+    --dry-run           Only print the summaries, don't write any files
+    --intervals-out     Write a table of the intervals next to every file
+    --debug-intervals   Print the cumulative start and end, duration and 
+                        running TSS of every interval to stderr
+    --list              Only list the worksheets and whether they are valid 
+                        workouts
+    --validate          Only check the worksheets against the template and 
+                        report all problems, fail if any has errors or 
+                        warnings
+    --merge <file name> Chain all workouts into a single file <file name>
+    --compare <file>    Only compare the TSS of the worksheets with the ones 
+                        of the same name in the previous version <file>, 
+                        fail if any changed
+    --force-ftp <watts> Change the FTP of all workouts to <watts> before 
+                        merging them, keeping their intensities
+    --split-on-markers  Write a file per segment of the workouts, which are 
+                        separated by comment rows
+    --max-rows <n>      Fail if an erg or mrc file would have more than <n> 
+                        data points (default: 1000000)
+    --max-watts <watts> Limit the written watts to <watts>
+    --max-watts-mode <clamp|recalculate|abort>
+                        What happens above --max-watts (default: clamp only 
+                        the written values)
+    --erg-version <1|2> VERSION line of the erg header (default: 2)
+    --format <erg|mrc|json|gc-json|zwo|hr-erg|fit>
+                        Output format (default: erg)
+    --fit-watts         Target absolute watts instead of percent of FTP in fit 
+                        files
+    --ftp <watts>       Use this FTP instead of the one in the worksheets
+    --ftp-file <path>   Read the FTP from <path>, which contains the number or 
+                        an ftp = <watts> line. --ftp takes precedence
+    --ftp-map <path>    Read the FTP of every worksheet from <path>, which has 
+                        <worksheet> = <watts> lines. --ftp takes precedence, 
+                        --ftp-file applies to the other worksheets
+    --hr-curve <rest>,<threshold>
+                        Heart rates in fractions of the maximum which 
+                        correspond to 0 W and FTP (default: 0.5,0.9)
+    --hr-max <bpm>      Use this maximum heart rate instead of the one in the 
+                        worksheets
+    --header-rows <n>   Number of rows before the data points (default: 
+                        detected)
+    --metadata-range <name>
+                        Read the header of xlsx worksheets from the cells of 
+                        the defined name <name>
+    --data-range <name> Read the data points of xlsx worksheets from the cells 
+                        of the defined name <name>
+    --name-template <template>
+                        Name the files after <template>, e.g. 
+                        {date}_{sheet}_{tss}.erg
+    --no-clobber        Don't overwrite existing files
+    --no-sort           Same as --sort none
+    --no-warn           Don't warn about workouts ending without cooldown
+    --preview           Draw the power profile of every workout in the terminal
+    --prompt-ftp        Ask for the FTP of worksheets without one, if running 
+                        in a terminal
+    --outdir <dir>      Directory the files are written to (default: .)
+    --ramp-resolution <seconds>
+                        Add a data point every <seconds> to ramps
+    --sample-interval <seconds>
+                        Resample the workouts every <seconds>, steady 
+                        intervals included, and calculate TSS from the samples
+    --report <path>     Write the outcome of every worksheet as json to <path>
+    --rounding <nearest|truncate|ceil>
+                        How watts are rounded (default: nearest)
+    --line-ending <lf|crlf>
+                        Line terminator of erg and mrc files (default: lf)
+    --smooth <window>   Smooth the written watts with a moving average over 
+                        <window> data points
+    --smooth-tss        Calculate TSS and NP from the smoothed watts
+    --since <YYYY-MM-DD>
+                        Skip worksheets dated before this date, by a DATE 
+                        cell or the worksheet name
+    --skip-undated      Skip worksheets without date as well with --since
+    --sort <lexical|natural|none>
+                        Order of the worksheets, natural sorts Week2 before 
+                        Week10 and none keeps the workbook order (default: 
+                        lexical)
+    --sheet <name>      Only convert the worksheet <name>, can be repeated
+    --match <pattern>   Only convert the worksheets matching <pattern>, where 
+                        * matches any characters and ? a single one. Can be 
+                        repeated
+    --verify            Read the written erg files back and check their TSS
+    --stdout            Write the workout to stdout instead of a file, 
+                        requires a single selected worksheet
+    --skip-sheet <name> Don't convert the worksheet <name>, can be repeated. 
+                        Replaces the default (Overview)
+    --strict            End the data at the first empty row instead of skipping 
+                        empty rows, fail instead of warning and instead of 
+                        numbering duplicate file names
+    --summary-format <plain|tsv|markdown>
+                        Layout of the summaries (default: plain)
+    --tss-decimals <n>  Decimal places of the TSS in the summaries, at most 
+                        3 (default: 0)
+    --tss-scale <points>
+                        TSS of one hour at FTP (default: 100)
+    --target-duration <minutes>
+                        Pad shorter workouts to <minutes> with a longer 
+                        cooldown, warn about longer ones
+    --trim              Cut off workouts longer than --target-duration
+    --time-precision <n>
+                        Decimal places of the time column (default: 2)
+    --time-unit <minutes|seconds>
+                        Unit of the time column of the worksheets and csv 
+                        files (default: minutes)
+    --time-col <column> Column of the times in the worksheets, a letter or a 
+                        0-based index (default: A)
+    --intensity-col <column>
+                        Column of the intensities in the worksheets 
+                        (default: B)
+    --time-format <minutes|mm:ss>
+                        Notation of the time column (default: minutes)
+    --watt-floor <watts>
+                        Raise the written watts below <watts> to it
+    --watt-floor-tss    Calculate TSS and NP from the raised watts
+    --watts-mode        The intensity column contains absolute watts
+    --units <english|metric>
+                        Units declared in the file header, overrides the 
+                        units of the worksheets (default: english)";
+
+/// Log target of the output proper, i.e. the summaries, the details of `-v` 
+/// and the entries of `--list`
+const OUTPUT: &str = "output";
+
+/// Prints the records with the target `OUTPUT` to stdout and all diagnostic 
+/// messages, warnings and errors to stderr, so the summaries can be piped 
+/// into a file. With `--stdout`, everything goes to stderr.
+struct Logger {
+    stderr_only: AtomicBool,
+}
+
+static LOGGER: Logger = Logger { stderr_only: AtomicBool::new(false) };
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => eprintln!("Error: {}", record.args()),
+            Level::Warn => eprintln!("Warning: {}", record.args()),
+            _ if record.target() == OUTPUT 
+                && !self.stderr_only.load(Ordering::Relaxed) => 
+                println!("{}", record.args()),
+            _ => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// What happens to data points above `--max-watts`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MaxWattsMode {
+    /// Clamp the written values, TSS and NP reflect the original ones
+    Clamp,
+    /// Also calculate TSS and NP from the clamped values
+    Recalculate,
+    /// Skip the workout
+    Abort,
+}
+
+impl FromStr for MaxWattsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(MaxWattsMode::Clamp),
+            "recalculate" => Ok(MaxWattsMode::Recalculate),
+            "abort" => Ok(MaxWattsMode::Abort),
+            _ => Err(format!("unknown max watts mode '{}'", s)),
+        }
+    }
+}
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Workbooks and `csv` files to convert
+    files: Vec<String>,
+    format: Format,
+    parse: ParseOptions,
+    /// Directory the converted files are written to
+    outdir: PathBuf,
+    /// Replaces the units declared in the worksheets
+    units: Option<Units>,
+    /// Interval in seconds between the points added to ramps
+    ramp_resolution: Option<f64>,
+    /// Interval in seconds between the data points of resampled workouts
+    sample_interval: Option<f64>,
+    /// Duration in minutes every workout is padded to
+    target_duration: Option<f64>,
+    /// Cut off workouts longer than `target_duration`
+    trim: bool,
+    /// Window of the moving average which is applied to the workout itself, 
+    /// so the `TSS` reflects the smoothed intensities
+    smooth_tss: Option<usize>,
+    /// Upper limit for the written watts
+    max_watts: Option<f64>,
+    max_watts_mode: MaxWattsMode,
+    /// Lower limit for the written watts
+    watt_floor: Option<f64>,
+    /// Calculate `TSS` from the watts raised to `watt_floor`
+    watt_floor_tss: bool,
+    /// Only parse the worksheets without writing any files
+    dry_run: bool,
+    /// Only list the worksheets
+    list: bool,
+    /// Only check the worksheets against the template
+    validate: bool,
+    /// File name of the single workout all workouts are chained into
+    merge: Option<String>,
+    /// Previous version of the workbook whose `TSS` is compared
+    compare: Option<String>,
+    /// Defined names of the metadata and data areas of the worksheets
+    metadata_range: Option<String>,
+    data_range: Option<String>,
+    /// FTP all workouts are changed to before they're merged
+    force_ftp: Option<f64>,
+    /// Convert the segments between the comment rows as workouts of their own
+    split_on_markers: bool,
+    /// Add the number of intervals to the summaries
+    detailed: bool,
+    summary_format: SummaryFormat,
+    /// Decimal places of the `TSS` in the summaries
+    tss_decimals: usize,
+    /// Ask for the FTP of workouts without one
+    prompt_ftp: bool,
+    /// Draw the power profile of the workouts on terminals
+    preview: bool,
+    /// FTPs of the worksheets, unless `--ftp` is given
+    ftp_map: Option<FtpMap>,
+    /// Don't warn about workouts without cooldown
+    no_warn: bool,
+    /// Names of the worksheets which aren't converted
+    skip_sheets: HashSet<String>,
+    /// Names of the worksheets to convert, all if empty (and no `patterns`)
+    sheets: HashSet<String>,
+    /// Glob patterns of the worksheets to convert
+    patterns: Vec<String>,
+    /// Skip worksheets dated before this date
+    since: Option<Date>,
+    /// Skip worksheets without date as well with `since`
+    skip_undated: bool,
+    sheet_order: SheetOrder,
+    /// Write the workout to stdout instead of a file
+    stdout: bool,
+    /// Read the written files back and compare their `TSS`
+    verify: bool,
+    /// Write the interval table next to every file
+    intervals_out: bool,
+    /// Print the cumulative and delta times of the intervals
+    debug_intervals: bool,
+    /// Replaces the file names of the worksheets, see `render_file_name`
+    name_template: Option<String>,
+    /// Path of the `json` report written at the end of the run
+    report: Option<PathBuf>,
+    /// Number of `-v` flags
+    verbosity: usize,
+    /// Only print warnings and errors
+    quiet: bool,
+    write: WriteOptions,
+}
+
+impl Options {
+    /// Returns whether `worksheet` has to be converted.
+    fn selected(&self, worksheet: &str) -> bool {
+        if self.skipped(worksheet) {
+            return false;
+        }
+        if self.sheets.is_empty() && self.patterns.is_empty() {
+            return true;
+        }
+        self.sheets.contains(worksheet) 
+            || self.patterns.iter()
+                .any(|pattern| glob_match(pattern, worksheet))
+    }
+
+    /// Returns whether `worksheet` is in the skip list, ignoring the case and 
+    /// surrounding whitespace, e.g. of `overview `.
+    fn skipped(&self, worksheet: &str) -> bool {
+        self.skip_sheets.iter().any(|skip| label_matches(worksheet, skip))
+    }
+
+    /// Returns the parse options of `worksheet`, whose FTP may be set by 
+    /// `--ftp-map`.
+    fn sheet_parse(&self, worksheet: &str) -> ParseOptions {
+        let ftp = self.ftp_map.as_ref().and_then(|map| map.ftp(worksheet));
+        ParseOptions { ftp: ftp.or(self.parse.ftp), ..self.parse.clone() }
+    }
+
+    /// Returns whether the worksheet (or `csv` file) `name` is dated 
+    /// `--since` or later. The date next to a `DATE` label of the worksheet 
+    /// takes precedence over one in its name. Undated worksheets are recent 
+    /// unless `--skip-undated` is given.
+    fn recent(&self, name: &str, range: Option<&Range>) -> bool {
+        let since = match self.since {
+            Some(since) => since,
+            None => return true,
+        };
+        match range.and_then(|range| sheet_date(range, self.parse.columns))
+            .or_else(|| find_date(name)) {
+            Some(date) => date >= since,
+            None => !self.skip_undated,
+        }
+    }
+}
+
+/// Parses the command line arguments (without the program name). Option 
+/// values can either be given as `--option=value` or `--option value`.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut files = Vec::new();
+    let mut format = Format::Erg;
+    let mut parse = ParseOptions::default();
+    let mut outdir = PathBuf::new();
+    let mut units = None;
+    let mut ramp_resolution = None;
+    let mut sample_interval = None;
+    let mut target_duration = None;
+    let mut ftp_file = None;
+    let mut ftp_map = None;
+    let mut trim = false;
+    let mut smooth_tss = false;
+    let mut max_watts = None;
+    let mut max_watts_mode = MaxWattsMode::Clamp;
+    let mut watt_floor = None;
+    let mut watt_floor_tss = false;
+    let mut dry_run = false;
+    let mut list = false;
+    let mut validate = false;
+    let mut merge = None;
+    let mut compare = None;
+    let mut force_ftp = None;
+    let mut split_on_markers = false;
+    let mut metadata_range = None;
+    let mut data_range = None;
+    let mut detailed = false;
+    let mut preview = false;
+    let mut summary_format = SummaryFormat::Plain;
+    let mut tss_decimals = 0;
+    let mut prompt_ftp = false;
+    let mut no_warn = false;
+    let mut skip_sheets = HashSet::new();
+    let mut sheets = HashSet::new();
+    let mut patterns = Vec::new();
+    let mut sheet_order = SheetOrder::default();
+    let mut since = None;
+    let mut skip_undated = false;
+    let mut stdout = false;
+    let mut verify = false;
+    let mut intervals_out = false;
+    let mut debug_intervals = false;
+    let mut name_template = None;
+    let mut report = None;
+    let mut verbosity = 0;
+    let mut quiet = false;
+    let mut write = WriteOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (&arg[..i], Some(&arg[i+1..])),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || inline_value.map(str::to_string)
+            .or_else(|| args.next().cloned())
+            .ok_or(format!("missing value for '{}'", name));
+
+        match name {
+            "-q" | "--quiet" => quiet = true,
+            "-v" | "--verbose" => verbosity += 1,
+            "-vv" => verbosity += 2,
+            "--annotate" => write.annotate = true,
+            "--dry-run" => dry_run = true,
+            "--list" => list = true,
+            "--validate" => validate = true,
+            "--merge" => merge = Some(value()?),
+            "--compare" => compare = Some(value()?),
+            "--split-on-markers" => split_on_markers = true,
+            "--detailed" => detailed = true,
+            "--preview" => preview = true,
+            "--summary-format" => summary_format = value()?.parse()?,
+            "--tss-decimals" => {
+                let value = value()?;
+                tss_decimals = match value.parse() {
+                    Ok(decimals) if decimals <= 3 => decimals,
+                    _ => return Err(format!("invalid number of decimals '{}'", 
+                                            value)),
+                };
+            },
+            "--prompt-ftp" => prompt_ftp = true,
+            "--no-warn" => no_warn = true,
+            "--format" => format = value()?.parse()?,
+            "--desc-prefix" => write.desc_prefix = value()?,
+            "--desc-suffix" => write.desc_suffix = value()?,
+            "--erg-version" => {
+                let value = value()?;
+                write.erg_version = match value.parse() {
+                    Ok(version) if ERG_VERSIONS.contains(&version) => version,
+                    _ => return Err(format!("unsupported erg version '{}', \
+                                            expected one of {:?}", value, 
+                                            ERG_VERSIONS)),
+                };
+            },
+            "--ftp" => {
+                let value = value()?;
+                parse.ftp = match value.parse() {
+                    Ok(ftp) if ftp > 0.0 => Some(ftp),
+                    _ => return Err(format!("invalid FTP '{}'", value)),
+                };
+            },
+            "--fit-watts" => write.fit_watts = true,
+            "--force-ftp" => {
+                let value = value()?;
+                force_ftp = match value.parse() {
+                    Ok(ftp) if ftp > 0.0 => Some(ftp),
+                    _ => return Err(format!("invalid FTP '{}'", value)),
+                };
+            },
+            "--ftp-file" => ftp_file = Some(value()?),
+            "--ftp-map" => ftp_map = Some(value()?),
+            "--hr-curve" => parse.hr_curve = value()?.parse()?,
+            "--hr-max" => {
+                let value = value()?;
+                parse.hr_max = match value.parse() {
+                    Ok(bpm) if bpm > 0.0 => Some(bpm),
+                    _ => return Err(format!("invalid maximum heart rate '{}'", 
+                                            value)),
+                };
+            },
+            "--time-unit" => parse.time_unit = value()?.parse()?,
+            "--time-col" => parse.columns.time = parse_column(&value()?)?,
+            "--intensity-col" => 
+                parse.columns.intensity = parse_column(&value()?)?,
+            "--metadata-range" => metadata_range = Some(value()?),
+            "--data-range" => data_range = Some(value()?),
+            "--header-rows" => {
+                let value = value()?;
+                parse.header_rows = Some(value.parse()
+                    .map_err(|_| format!("invalid header rows '{}'", value))?);
+            },
+            "--no-clobber" => write.no_clobber = true,
+            "--max-watts" => {
+                let value = value()?;
+                max_watts = match value.parse() {
+                    Ok(watts) if watts > 0.0 => Some(watts),
+                    _ => return Err(format!("invalid max watts '{}'", value)),
+                };
+            },
+            "--max-rows" => {
+                let value = value()?;
+                write.max_rows = value.parse()
+                    .map_err(|_| format!("invalid max rows '{}'", value))?;
+            },
+            "--max-watts-mode" => max_watts_mode = value()?.parse()?,
+            "--watt-floor" => {
+                let value = value()?;
+                watt_floor = match value.parse() {
+                    Ok(watts) if watts > 0.0 => Some(watts),
+                    _ => return Err(format!("invalid watt floor '{}'", value)),
+                };
+            },
+            "--watt-floor-tss" => watt_floor_tss = true,
+            "--outdir" => outdir = PathBuf::from(value()?),
+            "--sheet" => { sheets.insert(value()?); },
+            "--match" => patterns.push(value()?),
+            "--since" => since = Some(value()?.parse()?),
+            "--skip-undated" => skip_undated = true,
+            "--sort" => sheet_order = value()?.parse()?,
+            "--no-sort" => sheet_order = SheetOrder::Workbook,
+            "--rounding" => write.rounding = value()?.parse()?,
+            "--line-ending" => write.line_ending = value()?.parse()?,
+            "--strict" => parse.strict = true,
+            "--watts-mode" => parse.watts = true,
+            "--stdout" => stdout = true,
+            "--verify" => verify = true,
+            "--intervals-out" => intervals_out = true,
+            "--debug-intervals" => debug_intervals = true,
+            "--report" => report = Some(PathBuf::from(value()?)),
+            "--name-template" => {
+                let template = value()?;
+                // report unknown placeholders before converting anything
+                render_file_name(&template, &Workout::default(), "", "")?;
+                name_template = Some(template);
+            },
+            "--skip-sheet" => { skip_sheets.insert(value()?); },
+            "--time-precision" => {
+                let value = value()?;
+                write.time_precision = value.parse()
+                    .map_err(|_| format!("invalid time precision '{}'", 
+                                         value))?;
+            },
+            "--tss-scale" => {
+                let value = value()?;
+                parse.tss_scale = match value.parse() {
+                    Ok(scale) if scale > 0.0 => Some(scale),
+                    _ => return Err(format!("invalid TSS scale '{}'", value)),
+                };
+            },
+            "--time-format" => write.time_format = value()?.parse()?,
+            "--units" => units = Some(value()?.parse()?),
+            "--ramp-resolution" => {
+                let value = value()?;
+                ramp_resolution = match value.parse() {
+                    Ok(seconds) if seconds > 0.0 => Some(seconds),
+                    _ => return Err(format!("invalid ramp resolution '{}'", 
+                                            value)),
+                };
+            },
+            "--sample-interval" => {
+                let value = value()?;
+                sample_interval = match value.parse() {
+                    Ok(seconds) if seconds > 0.0 => Some(seconds),
+                    _ => return Err(format!("invalid sample interval '{}'", 
+                                            value)),
+                };
+            },
+            "--target-duration" => {
+                let value = value()?;
+                target_duration = match value.parse() {
+                    Ok(minutes) if minutes > 0.0 => Some(minutes),
+                    _ => return Err(format!("invalid target duration '{}'", 
+                                            value)),
+                };
+            },
+            "--trim" => trim = true,
+            "--smooth" => {
+                let value = value()?;
+                write.smooth = match value.parse() {
+                    Ok(window) if window > 0 => Some(window),
+                    _ => return Err(format!("invalid smoothing window '{}'", 
+                                            value)),
+                };
+            },
+            "--smooth-tss" => smooth_tss = true,
+            _ if name.starts_with("--") => 
+                return Err(format!("unknown option '{}'", arg)),
+            _ => files.push(arg.to_string()),
+        }
+    }
+
+    // --ftp takes precedence over the FTPs of the map, which take precedence 
+    // over the shared FTP, which takes precedence over the worksheets
+    let ftp_map = match (parse.ftp, &ftp_map) {
+        (None, Some(path)) => Some(fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {}", path, e))?
+            .parse::<FtpMap>()
+            .map_err(|e| format!("{}: {}", path, e))?),
+        _ => None,
+    };
+    if let (None, Some(path)) = (parse.ftp, &ftp_file) {
+        let config = fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {}", path, e))?;
+        parse.ftp = Some(parse_ftp_config(&config)
+            .map_err(|e| format!("{}: {}", path, e))?);
+    }
+
+    if smooth_tss && write.smooth.is_none() {
+        return Err("--smooth-tss requires --smooth".to_string());
+    }
+    // the workout is smoothed before writing it then
+    let smooth_tss = if smooth_tss { write.smooth.take() } else { None };
+    if parse.columns.time == parse.columns.intensity {
+        return Err("--time-col and --intensity-col have to differ"
+                   .to_string());
+    }
+    if force_ftp.is_some() && merge.is_none() {
+        return Err("--force-ftp requires --merge".to_string());
+    }
+    if split_on_markers && (stdout || merge.is_some()) {
+        return Err("--split-on-markers can't be combined with --stdout or \
+                   --merge".to_string());
+    }
+    if let Some(other) = &compare {
+        if list || validate || merge.is_some() || stdout {
+            return Err("--compare can't be combined with --list, --validate, \
+                       --merge or --stdout".to_string());
+        }
+        let workbook = |file: &str| input_kind(file) == Ok(Input::Workbook);
+        if files.len() != 1 || !workbook(&files[0]) || !workbook(other) {
+            return Err("--compare requires a single workbook and the workbook \
+                       to compare it with".to_string());
+        }
+    }
+    // the resampled ramps already have a point at every sample
+    if sample_interval.is_some() && ramp_resolution.is_some() {
+        return Err("--sample-interval can't be combined with \
+                   --ramp-resolution".to_string());
+    }
+    if watt_floor_tss && watt_floor.is_none() {
+        return Err("--watt-floor-tss requires --watt-floor".to_string());
+    }
+    if let (Some(floor), Some(max_watts)) = (watt_floor, max_watts) {
+        if floor > max_watts {
+            return Err(format!("--watt-floor {} is above --max-watts {}", 
+                               floor, max_watts));
+        }
+    }
+
+    let single_sheet = files.first()
+        .is_some_and(|file| is_single_workout(file))
+        || (sheets.len() == 1 && patterns.is_empty());
+    if stdout && merge.is_none() && (files.len() != 1 || !single_sheet) {
+        return Err("--stdout requires a single file and worksheet (--sheet) \
+                   or --merge".to_string());
+    }
+    // the xls reader of the office crate prints debug output to stdout
+    if stdout && files.iter()
+        .any(|file| file.to_ascii_lowercase().ends_with(".xls")) {
+        return Err("--stdout doesn't support xls files".to_string());
+    }
+
+    if intervals_out && stdout {
+        return Err("--intervals-out can't be combined with --stdout"
+                   .to_string());
+    }
+    if verify && (stdout || format != Format::Erg) {
+        return Err("--verify requires erg files (--format erg without \
+                   --stdout)".to_string());
+    }
+
+    Ok(Options {
+        files: if files.is_empty() {
+            return Err("missing input file".to_string());
+        } else {
+            files
+        },
+        format,
+        parse,
+        outdir,
+        units,
+        ramp_resolution,
+        sample_interval,
+        target_duration,
+        trim,
+        smooth_tss,
+        max_watts,
+        max_watts_mode,
+        watt_floor,
+        watt_floor_tss,
+        dry_run,
+        list,
+        validate,
+        merge,
+        compare,
+        force_ftp,
+        split_on_markers,
+        metadata_range,
+        data_range,
+        detailed,
+        summary_format,
+        tss_decimals,
+        preview,
+        prompt_ftp,
+        ftp_map,
+        no_warn,
+        skip_sheets: if skip_sheets.is_empty() {
+            ["Overview".to_string()].iter().cloned().collect()
+        } else {
+            skip_sheets
+        },
+        sheets,
+        patterns,
+        sheet_order,
+        since,
+        skip_undated,
+        stdout,
+        verify,
+        intervals_out,
+        debug_intervals,
+        name_template,
+        report,
+        verbosity,
+        quiet,
+        write,
+    })
+}
+
+/// Outcome of `convert`
+struct Conversion {
+    /// The workout, `None` if it couldn't be parsed
+    workout: Option<Workout>,
+    /// Entry of the `--report`
+    report: SheetReport,
+}
+
+/// Returns how the workout of `file` and `worksheet` (unless it's a `csv` 
+/// file) is called in messages. The file is only mentioned when there are 
+/// several.
+fn workout_name(file: &str, worksheet: Option<&str>, options: &Options) 
+    -> String {
+    match worksheet {
+        Some(worksheet) if options.files.len() > 1 => 
+            format!("{}: worksheet {}", file, worksheet),
+        Some(worksheet) => format!("worksheet {}", worksheet),
+        None => file.to_string(),
+    }
+}
+
+/// Converts a parsed workout, see `convert_workout`, or with 
+/// `--split-on-markers` each of its segments. `file` and `worksheet` (unless 
+/// it's a `csv` file) identify the workout in messages.
+fn convert(file: &str, worksheet: Option<&str>, 
+           parsed: Result<Workout, ParseError>, options: &Options) 
+    -> Vec<Conversion> {
+    let workout = match parsed {
+        Ok(workout) => workout,
+        Err(e) => {
+            error!("Skipping {}: {}", workout_name(file, worksheet, options), 
+                   e);
+            return vec![Conversion {
+                workout: None,
+                report: SheetReport::failed(file, worksheet, &e),
+            }];
+        },
+    };
+    let mut segments = if options.split_on_markers {
+        split_workout(&workout)
+    } else {
+        Vec::new()
+    };
+    if segments.len() < 2 {
+        return vec![convert_workout(file, worksheet, None, workout, options)];
+    }
+    // the warnings of the whole worksheet are reported once
+    segments[0].warnings = workout.warnings;
+    segments.into_iter().enumerate()
+        .map(|(i, segment)| 
+             convert_workout(file, worksheet, Some(i + 1), segment, options))
+        .collect()
+}
+
+/// Applies the `options` to a workout, writes it and prints its summary 
+/// unless it's merged later. `segment` is the number of the segment of a 
+/// split workout.
+fn convert_workout(file: &str, worksheet: Option<&str>, segment: Option<usize>, 
+                   mut workout: Workout, options: &Options) -> Conversion {
+    let failed = |e: &dyn std::fmt::Display| Conversion {
+        workout: None,
+        report: SheetReport::failed(file, worksheet, e),
+    };
+    let mut name = workout_name(file, worksheet, options);
+    if let Some(segment) = segment {
+        name = format!("{}, segment {}", name, segment);
+    }
+    let source = if options.files.len() > 1 { Some(file) } else { None };
+
+    // before the data points are interpolated, `set_ftp` pairs them up
+    if let Some(ftp) = options.force_ftp {
+        if (workout.ftp - ftp).abs() > FTP_TOLERANCE {
+            warn!("{}: changed the FTP from {} to {} W", name, workout.ftp, 
+                  ftp);
+        }
+        workout.set_ftp(ftp);
+    }
+    if let Some(minutes) = options.target_duration {
+        workout.fit_duration(minutes, options.trim);
+    }
+    // the parser already failed on its own warnings
+    if let (true, Some(warning)) = (options.parse.strict, 
+                                    workout.warnings.pop()) {
+        let e = ParseError::Strict { warning };
+        error!("Skipping {}: {}", name, e);
+        return failed(&e);
+    }
+    for warning in &workout.warnings {
+        if options.no_warn && matches!(warning, Warning::NoCooldown { .. }) {
+            continue;
+        }
+        warn!("{}: {}", name, warning);
+    }
+    if let Some(units) = options.units {
+        workout.units = units;
+    }
+    if let Some(max_watts) = options.max_watts {
+        let peak = workout.workout_data.iter()
+            .map(|data| data.intensity * workout.ftp)
+            .fold(0.0, f64::max);
+        if peak > max_watts {
+            match options.max_watts_mode {
+                MaxWattsMode::Abort => {
+                    let e = format!("{:.0} W is above the limit of {} W", 
+                                    peak, max_watts);
+                    error!("Skipping {}: {}", name, e);
+                    return failed(&e);
                 },
-                _ => println!("Error in dataset"),
+                MaxWattsMode::Clamp | MaxWattsMode::Recalculate => 
+                    warn!("{}: clamped {:.0} W to {} W", name, peak, max_watts),
             }
         }
-        
-        assert!(workout.workout_data.len() % 2 == 0);
-
-        let mut tss = 0.0;
-        for i in 0..workout.workout_data.len() {
-            if i % 2 == 0 {
-                let interval = 
-                    Interval::new(&workout.workout_data[i], 
-                                  &workout.workout_data[i+1], workout.ftp);
-                tss += interval.tss;
-                workout.intervals.push(interval);
+        // ramps are interpolated from the clamped points, unless the TSS 
+        // has to reflect the original values
+        if options.max_watts_mode == MaxWattsMode::Recalculate {
+            workout.clamp_watts(max_watts);
+            workout.recalculate();
+        }
+    }
+    if let (Some(floor), true) = (options.watt_floor, options.watt_floor_tss) {
+        workout.floor_watts(floor);
+        workout.recalculate();
+    }
+    if let Some(window) = options.smooth_tss {
+        workout.smooth(window);
+        workout.recalculate();
+    }
+    if let Some(seconds) = options.ramp_resolution {
+        workout.interpolate_ramps(seconds / 60.0);
+    }
+    if let Some(seconds) = options.sample_interval {
+        workout.resample(seconds / 60.0);
+    }
+    if let Some(max_watts) = options.max_watts {
+        workout.clamp_watts(max_watts);
+    }
+    // counts the written data points, including the ones of ramps
+    if let Some(floor) = options.watt_floor {
+        FLOORED.fetch_add(workout.floor_watts(floor), Ordering::Relaxed);
+    }
+    if let Some(template) = &options.name_template {
+        let sheet = worksheet.map_or_else(
+            || Path::new(file).file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into()),
+            str::to_string);
+        // the template was checked by `parse_args`
+        if let Ok(file_name) = render_file_name(template, &workout, &sheet, 
+                                                &today()) {
+            workout.file_name = match segment {
+                Some(segment) => numbered_file_name(&file_name, segment),
+                None => file_name,
+            };
+        }
+    }
+    if !options.stdout && options.merge.is_none() {
+        workout.file_name = match claim_file_name(&workout, &name, options) {
+            Ok(file_name) => file_name,
+            Err(e) => {
+                error!("Skipping {}: {}", name, e);
+                return failed(&e);
+            },
+        };
+        // an imported erg file converted to erg again
+        let path = output_path(&options.outdir, &workout.file_name, 
+                               options.format.extension());
+        if let (Ok(path), Ok(input)) = (path.canonicalize(), 
+                                        Path::new(file).canonicalize()) {
+            if path == input {
+                let e = "it would overwrite the input file";
+                error!("Skipping {}: {}", name, e);
+                return failed(&e);
             }
         }
-        workout.tss = tss;
     }
-    workout
+    let report = if options.merge.is_some() {
+        SheetReport::converted(file, worksheet, &workout, None)
+    } else if options.dry_run {
+        print_summary(&workout, options, source, None);
+        print_preview(&workout, options);
+        print_details(&workout, options);
+        SheetReport::converted(file, worksheet, &workout, None)
+    } else {
+        match write_workout(&workout, options) {
+            Ok(destination) => {
+                print_summary(&workout, options, source, Some(&destination));
+                print_preview(&workout, options);
+                print_details(&workout, options);
+                if options.verify {
+                    verify(&workout, &destination);
+                }
+                SheetReport::converted(file, worksheet, &workout, 
+                                       Some(&destination))
+            },
+            Err(e) => {
+                error!("Couldn't write {}: {}", name, e);
+                UNWRITTEN.fetch_add(1, Ordering::Relaxed);
+                // e.g. refused by `--no-clobber`, it's no part of the totals
+                return Conversion {
+                    workout: None,
+                    report: SheetReport::failed(file, worksheet, 
+                                                &format!("couldn't write: {}", 
+                                                         e)),
+                };
+            },
+        }
+    };
+    Conversion { workout: Some(workout), report }
+}
+
+/// Output files of this run and the workouts they are written for
+static CLAIMED: Mutex<BTreeMap<PathBuf, String>> = Mutex::new(BTreeMap::new());
+
+/// Claims the output file of the workout `name` and returns its file name. If 
+/// another workout already claimed it, e.g. a copied worksheet whose 
+/// `FILE NAME` wasn't changed, a number is appended to the file name instead 
+/// of overwriting the other file. With `--strict`, that's an error.
+fn claim_file_name(workout: &Workout, name: &str, options: &Options) 
+    -> Result<String, String> {
+    let extension = options.format.extension();
+    let path = output_path(&options.outdir, &workout.file_name, extension);
+    let mut claimed = CLAIMED.lock().unwrap_or_else(|e| e.into_inner());
+    let first = match claimed.get(&path) {
+        Some(first) => first.clone(),
+        None => {
+            claimed.insert(path, name.to_string());
+            return Ok(workout.file_name.clone());
+        },
+    };
+    if options.parse.strict {
+        return Err(format!("{} is already written for {}", path.display(), 
+                           first));
+    }
+    let (file_name, numbered) = (2..)
+        .map(|n| {
+            let file_name = numbered_file_name(&workout.file_name, n);
+            let path = output_path(&options.outdir, &file_name, extension);
+            (file_name, path)
+        })
+        .find(|(_, path)| !claimed.contains_key(path))
+        .expect("there are more numbers than workouts");
+    warn!("{}: {} is already written for {}, writing {} instead", name, 
+          path.display(), first, numbered.display());
+    claimed.insert(numbered, name.to_string());
+    Ok(file_name)
+}
+
+/// Writes the workout in the selected format to a file or stdout and returns 
+/// where it was written to. With `--intervals-out`, the interval table is 
+/// written next to the file.
+fn write_workout(workout: &Workout, options: &Options) -> io::Result<String> {
+    let (outdir, write) = (&options.outdir, &options.write);
+    if options.stdout {
+        let out = &mut io::stdout().lock();
+        match options.format {
+            Format::Erg => write_erg(workout, write, out),
+            Format::Mrc => write_mrc(workout, write, out),
+            Format::Json => write_json(workout, out),
+            Format::GcJson => write_gc_json(workout, write, out),
+            Format::Zwo => write_zwo(workout, out),
+            Format::HrErg => write_hr_erg(workout, write, out),
+            Format::Fit => write_fit(workout, write, out),
+        }.map(|()| "stdout".to_string())
+    } else {
+        let path = match options.format {
+            Format::Erg => write_erg_file(workout, outdir, write),
+            Format::Mrc => write_mrc_file(workout, outdir, write),
+            Format::Json => write_json_file(workout, outdir, write),
+            Format::GcJson => write_gc_json_file(workout, outdir, write),
+            Format::Zwo => write_zwo_file(workout, outdir, write),
+            Format::HrErg => write_hr_erg_file(workout, outdir, write),
+            Format::Fit => write_fit_file(workout, outdir, write),
+        }?;
+        if options.intervals_out {
+            write_intervals_file(workout, outdir, write)?;
+        }
+        Ok(path.display().to_string())
+    }
+}
+
+/// Chains the converted `workouts` into the single workout `file_name`, 
+/// writes it unless it's a dry run and prints its summary. Returns the merged 
+/// workout and where it was written to.
+fn merge(workouts: &[Workout], file_name: &str, options: &Options) 
+    -> Result<(Workout, Option<String>), Box<dyn Error>> {
+    let merged = merge_workouts(workouts, file_name)?;
+    let destination = if options.dry_run {
+        print_summary(&merged, options, None, None);
+        print_preview(&merged, options);
+        None
+    } else {
+        let destination = write_workout(&merged, options)
+            .map_err(|e| format!("couldn't write {}: {}", file_name, e))?;
+        print_summary(&merged, options, None, Some(&destination));
+        print_preview(&merged, options);
+        if options.verify {
+            verify(&merged, &destination);
+        }
+        Some(destination)
+    };
+    print_details(&merged, options);
+    Ok((merged, destination))
+}
+
+/// Returns the current date (UTC) as `YYYY-MM-DD`.
+fn today() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    Date::from_days((seconds / 86400) as i64).to_string()
+}
+
+/// Number of worksheets and `csv` files which weren't skipped by name, 
+/// including the ones older than `--since`
+static CANDIDATES: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of selected worksheets which couldn't be read
+static UNREADABLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of worksheets and `csv` files which failed `--validate`
+static INVALID: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of files which failed `--verify`
+static MISMATCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of data points raised to `--watt-floor`
+static FLOORED: AtomicUsize = AtomicUsize::new(0);
+
+/// Reads the `erg` file at `path` back and warns if its `TSS` differs from 
+/// the one of the workout by more than `VERIFY_TOLERANCE`.
+fn verify(workout: &Workout, path: &str) {
+    let written = fs::read_to_string(path).map_err(|e| e.to_string())
+        .and_then(|erg| parse_erg(&erg, &ParseOptions::default())
+                  .map_err(|e| e.to_string()));
+    // the erg file is read back with the standard scale
+    let scale = workout.tss_scale.unwrap_or(TSS_SCALE) / TSS_SCALE;
+    match written.map(|written| written.tss * scale) {
+        Ok(tss) if (tss - workout.tss).abs() 
+            <= workout.tss * VERIFY_TOLERANCE => 
+            debug!("{}: verified TSS of {:.1}", path, tss),
+        Ok(tss) => {
+            warn!("{}: TSS of the written file is {:.1} instead of {:.1}", 
+                  path, tss, workout.tss);
+            MISMATCHES.fetch_add(1, Ordering::Relaxed);
+        },
+        Err(e) => {
+            warn!("Couldn't verify {}: {}", path, e);
+            MISMATCHES.fetch_add(1, Ordering::Relaxed);
+        },
+    }
+}
+
+/// Returns the summary line of the workout, with `--detailed` including the 
+/// number of intervals.
+fn summary(workout: &Workout, options: &Options, source: Option<&str>, 
+           destination: Option<&str>) -> String {
+    let format = options.summary_format;
+    // the columns of tables have to line up with the header
+    let table = format != SummaryFormat::Plain;
+    let mut cells = Vec::new();
+    if options.files.len() > 1 && (table || source.is_some()) {
+        cells.push(source.unwrap_or_default().to_string());
+    }
+    cells.extend(summary_cells(workout, format, options.detailed, 
+                               options.tss_decimals));
+    if !options.dry_run && (table || destination.is_some()) {
+        cells.push(destination.unwrap_or_default().to_string());
+    }
+    format.join(&cells)
+}
+
+/// Prints the header of the summaries (unless they are plain) before the 
+/// first one
+static SUMMARY_HEADER: Once = Once::new();
+
+/// Prints the summary line of the workout, see `summary`.
+fn print_summary(workout: &Workout, options: &Options, source: Option<&str>, 
+                 destination: Option<&str>) {
+    SUMMARY_HEADER.call_once(|| {
+        let mut labels = Vec::new();
+        if options.files.len() > 1 {
+            labels.push("SOURCE");
+        }
+        labels.extend(summary_labels(options.detailed));
+        if !options.dry_run {
+            labels.push("OUTPUT");
+        }
+        if let Some(header) = options.summary_format.header(&labels) {
+            info!(target: OUTPUT, "{}", header);
+        }
+    });
+    info!(target: OUTPUT, "{}", summary(workout, options, source, 
+                                        destination));
+}
+
+/// Lines of the power profile of `--preview`
+const PREVIEW_HEIGHT: usize = 8;
+
+/// Draws the power profile of the workout with `--preview` as wide as the 
+/// terminal (`COLUMNS`, 80 by default). It's a single message, so the charts 
+/// of worksheets converted in parallel don't interleave. Nothing is drawn 
+/// unless stderr is a terminal.
+fn print_preview(workout: &Workout, options: &Options) {
+    if !options.preview || !io::stderr().is_terminal() {
+        return;
+    }
+    let width = env::var("COLUMNS").ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80);
+    let chart = power_profile(workout, width, PREVIEW_HEIGHT);
+    if !chart.is_empty() {
+        info!("{}", chart.join("\n"));
+    }
+}
+
+/// Prints the time in zones and the intervals of the workout with `-v`, and 
+/// the interval dump to stderr with `--debug-intervals`. Like the preview, 
+/// the dump is a single message.
+fn print_details(workout: &Workout, options: &Options) {
+    debug!(target: OUTPUT, "    {}", workout.zone_breakdown());
+    for interval in &workout.intervals {
+        debug!(target: OUTPUT, "    {}", interval);
+    }
+    if options.debug_intervals {
+        info!("{}:\n{}", workout.file_name, 
+              interval_dump(workout).join("\n"));
+    }
+}
+
+/// Prints `name` tagged with `OK` or `INVALID` and the reason for `--list`.
+fn list_entry(name: &str, parsed: Result<Workout, ParseError>) {
+    match parsed {
+        Ok(_) => info!(target: OUTPUT, "{:8} {}", "OK", name),
+        Err(e) => info!(target: OUTPUT, "{:8} {}: {}", "INVALID", name, e),
+    }
+}
+
+/// Prints `name` tagged with `PASS` or `FAIL` and its problems, one per line, 
+/// for `--validate`.
+fn validate_entry(name: &str, problems: &[ParseError]) {
+    if problems.is_empty() {
+        info!(target: OUTPUT, "{:8} {}", "PASS", name);
+        return;
+    }
+    INVALID.fetch_add(1, Ordering::Relaxed);
+    let problems: Vec<String> = problems.iter()
+        .map(|problem| match problem {
+            ParseError::Strict { warning } => format!("warning: {}", warning),
+            error => format!("error: {}", error),
+        })
+        .collect();
+    info!(target: OUTPUT, "{:8} {}\n{:8} {}", "FAIL", name, "", 
+          problems.join(&format!("\n{:8} ", "")));
+}
+
+/// Parses the selected worksheets of the workbook `file` and returns their 
+/// names and `TSS`. Worksheets which can't be parsed are left out.
+fn workbook_tss(file: &str, options: &Options) 
+    -> Result<Vec<(String, f64)>, String> {
+    let (mut workbook, mut worksheets, names) = open_workbook(file, options)?;
+    options.sheet_order.sort(&mut worksheets);
+    let mut tss = Vec::new();
+    for worksheet in worksheets.into_iter()
+        .filter(|worksheet| options.selected(worksheet)) {
+        let parsed = read_range(&mut workbook, &worksheet, &names, options)
+            .map_err(|reason| ParseError::Unreadable { reason })
+            .and_then(|range| 
+                parse_range(&range, &options.sheet_parse(&worksheet)));
+        match parsed {
+            Ok(workout) => tss.push((worksheet, workout.tss)),
+            Err(e) => warn!("{}: skipping worksheet {}: {}", file, worksheet, 
+                            e),
+        }
+    }
+    Ok(tss)
+}
+
+/// Prints the `TSS` of the worksheets of the workbook `new` next to the one 
+/// of the same worksheets in its previous version `old` for `--compare`. 
+/// Fails if the `TSS` of any worksheet changed or a worksheet was added or 
+/// removed.
+fn compare(old: &str, new: &str, options: &Options) 
+    -> Result<(), Box<dyn Error>> {
+    let changes = compare_tss(&workbook_tss(old, options)?, 
+                              &workbook_tss(new, options)?);
+    let (old_name, new_name) = (file_name(old), file_name(new));
+    for line in comparison_table(&changes, &old_name, &new_name, 
+                                 options.tss_decimals) {
+        info!(target: OUTPUT, "{}", line);
+    }
+    let changed = changes.iter()
+        .filter(|change| change.changed(options.tss_decimals))
+        .count();
+    if changed > 0 {
+        return Err(format!("{} worksheet(s) changed", changed).into());
+    }
+    Ok(())
+}
+
+/// Returns the file name of the path `file` for messages.
+fn file_name(file: &str) -> String {
+    Path::new(file).file_name()
+        .map_or(file.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+/// Serializes the FTP prompts of worksheets converted in parallel
+static PROMPT: Mutex<()> = Mutex::new(());
+
+/// Asks for the FTP of the workout `name` on the terminal. Returns `None` if 
+/// stdin isn't a terminal or the answer isn't a number.
+fn prompt_ftp(name: &str) -> Option<f64> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    let _prompt = PROMPT.lock().unwrap_or_else(|e| e.into_inner());
+    eprint!("Enter FTP for {}: ", name);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    answer.trim().parse().ok()
+}
+
+/// Parses a workout with `parse` and the parse options `base`. With 
+/// `--prompt-ftp`, a workout without FTP is parsed again with the FTP entered 
+/// for it, which is validated like the one of a worksheet.
+fn with_prompted_ftp<F>(name: &str, base: &ParseOptions, options: &Options, 
+                        parse: F) -> Result<Workout, ParseError> 
+    where F: Fn(&ParseOptions) -> Result<Workout, ParseError> {
+    match parse(base) {
+        Err(ParseError::MissingFtp) if options.prompt_ftp => 
+            match prompt_ftp(name) {
+                Some(ftp) => parse(&ParseOptions { 
+                    ftp: Some(ftp), 
+                    ..base.clone() 
+                }),
+                None => Err(ParseError::MissingFtp),
+            },
+        parsed => parsed,
+    }
+}
+
+/// Kinds of input files
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    /// `xlsx` or legacy `xls` workbook, both are read by `Excel::open`
+    Workbook,
+    Csv,
+    /// erg file which was written before, e.g. by an older version
+    Erg,
+}
+
+/// Returns the kind of `file` by its extension.
+fn input_kind(file: &str) -> Result<Input, String> {
+    let extension = Path::new(file).extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("xlsx") | Some("xlsm") | Some("xls") => Ok(Input::Workbook),
+        Some("csv") => Ok(Input::Csv),
+        Some("erg") => Ok(Input::Erg),
+        _ => Err(format!("unsupported file {}, expected an xlsx, xls, csv or \
+                         erg file", file)),
+    }
+}
+
+/// Returns whether `file` holds a single workout instead of worksheets.
+fn is_single_workout(file: &str) -> bool {
+    matches!(input_kind(file), Ok(Input::Csv) | Ok(Input::Erg))
+}
+
+/// Opens the workbook `file` and returns it with the names of its worksheets 
+/// and, with `--metadata-range` or `--data-range`, its defined names.
+fn open_workbook(file: &str, options: &Options) 
+    -> Result<(Excel, Vec<String>, Vec<DefinedName>), String> {
+    let mut workbook = Excel::open(file)
+        .map_err(|e| format!("couldn't open {}: {}", file, e))?;
+    let existing = workbook.sheet_names()
+        .map_err(|e| format!("couldn't read worksheets of {}: {}", file, e))?;
+    let names = if options.metadata_range.is_some() 
+        || options.data_range.is_some() {
+        read_defined_names(Path::new(file))
+            .map_err(|e| format!("couldn't read the defined names of {}: {}", 
+                                 file, e))?
+    } else {
+        Vec::new()
+    };
+    for name in options.metadata_range.iter().chain(&options.data_range) {
+        if !names.iter()
+            .any(|defined| defined.name.eq_ignore_ascii_case(name)) {
+            warn!("{} has no defined name {}", file, name);
+        }
+    }
+    Ok((workbook, existing, names))
+}
+
+/// Reads the cells of `worksheet`, limited to the defined `names` of 
+/// `--metadata-range` and `--data-range`. Worksheets without them keep their 
+/// layout.
+fn read_range(workbook: &mut Excel, worksheet: &str, names: &[DefinedName], 
+              options: &Options) -> Result<Range, String> {
+    let range = workbook.worksheet_range(worksheet)
+        .map_err(|e| e.to_string())?;
+    let area = |name: &Option<String>| name.as_ref()
+        .and_then(|name| named_area(names, worksheet, name));
+    Ok(named_range(&range, area(&options.metadata_range), 
+                   area(&options.data_range)))
+}
+
+/// Number of workouts whose file couldn't be written
+static UNWRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Converts all worksheets of the workbook (or the `csv` or `erg` file) 
+/// `file`. The worksheets are converted in parallel, so their summaries may 
+/// be printed in any order. Returns the outcomes in the order of the 
+/// worksheets. Nothing is converted with `--list`.
+fn convert_file(file: &str, options: &Options) 
+    -> Result<Vec<Conversion>, Box<dyn Error>> {
+    if (options.list || options.validate) && options.files.len() > 1 {
+        info!(target: OUTPUT, "{}:", file);
+    }
+
+    let kind = input_kind(file)?;
+    if kind != Input::Workbook {
+        let text = fs::read_to_string(file)
+            .map_err(|e| format!("couldn't open {}: {}", file, e))?;
+        let parse_text = |parse: &ParseOptions| if kind == Input::Erg {
+            parse_erg(&text, parse)
+        } else {
+            parse_workout_csv(&text, parse)
+        };
+        if options.validate {
+            let problems = match parse_text(&options.parse) {
+                Ok(workout) => workout.warnings.into_iter()
+                    .map(|warning| ParseError::Strict { warning })
+                    .collect(),
+                Err(e) => vec![e],
+            };
+            validate_entry(file, &problems);
+            return Ok(Vec::new());
+        }
+        if options.list {
+            list_entry(file, parse_text(&options.parse));
+            return Ok(Vec::new());
+        }
+        CANDIDATES.fetch_add(1, Ordering::Relaxed);
+        if !options.recent(file, None) {
+            info!("Skipping {}, it's older than {}", file, 
+                  options.since.map_or(String::new(), |date| date.to_string()));
+            return Ok(Vec::new());
+        }
+        let parsed = with_prompted_ftp(file, &options.parse, options, 
+                                       parse_text);
+        Ok(convert(file, None, parsed, options))
+    } else {
+        let (mut workbook, existing, names) = open_workbook(file, options)?;
+
+        let mut worksheets = existing.clone();
+        // selected worksheets which don't exist fail to parse
+        for sheet in &options.sheets {
+            if !worksheets.contains(sheet) {
+                worksheets.push(sheet.clone());
+            }
+        }
+        options.sheet_order.sort(&mut worksheets);
+
+        if options.list || options.validate {
+            for worksheet in worksheets {
+                if !options.selected(&worksheet) {
+                    info!(target: OUTPUT, "{:8} {}", "SKIP", worksheet);
+                    continue;
+                }
+                let parse = options.sheet_parse(&worksheet);
+                let range = read_range(&mut workbook, &worksheet, &names, 
+                                       options)
+                    .map_err(|_| ParseError::SheetNotFound);
+                if options.validate {
+                    let problems = match range {
+                        Ok(range) => validate_range(&range, &parse),
+                        Err(e) => vec![e],
+                    };
+                    validate_entry(&worksheet, &problems);
+                } else {
+                    list_entry(&worksheet, range
+                               .and_then(|range| parse_range(&range, &parse)));
+                }
+            }
+            return Ok(Vec::new());
+        }
+
+        let (selected, unselected): (Vec<_>, Vec<_>) = worksheets.into_iter()
+            .filter(|worksheet| !options.skipped(worksheet))
+            .partition(|worksheet| options.selected(worksheet));
+        if !unselected.is_empty() {
+            info!("Not selected: {}", unselected.join(", "));
+        }
+        CANDIDATES.fetch_add(selected.len(), Ordering::Relaxed);
+
+        // the workbook can't be shared between threads, so the cells are read 
+        // up front and only parsing and writing runs in parallel
+        let ranges: Vec<_> = selected.into_iter()
+            .map(|worksheet| {
+                let range = read_range(&mut workbook, &worksheet, &names, 
+                                       options)
+                    .map_err(|reason| if existing.contains(&worksheet) {
+                        ParseError::Unreadable { reason }
+                    } else {
+                        ParseError::SheetNotFound
+                    });
+                if range.is_err() {
+                    UNREADABLE.fetch_add(1, Ordering::Relaxed);
+                }
+                (worksheet, range)
+            })
+            .collect::<Vec<_>>();
+
+        // worksheets which can't be read are reported regardless of the date
+        let (ranges, old): (Vec<_>, Vec<_>) = ranges.into_iter()
+            .partition(|(worksheet, range)| range.as_ref()
+                .map_or(true, |range| options.recent(worksheet, Some(range))));
+        if let Some(since) = options.since.filter(|_| !old.is_empty()) {
+            let old: Vec<_> = old.iter()
+                .map(|(worksheet, _)| worksheet.as_str())
+                .collect();
+            let undated = if options.skip_undated { " or undated" } else { "" };
+            info!("Older than {}{}: {}", since, undated, old.join(", "));
+        }
+
+        // report the progress on terminals only, it would clutter logs
+        let progress = !options.quiet && io::stderr().is_terminal();
+        let total = ranges.len();
+
+        // parse content and write the files, `collect` keeps the order
+        Ok(ranges.into_par_iter()
+            .enumerate()
+            .flat_map_iter(|(i, (worksheet, range))| {
+                if progress {
+                    eprintln!("[{}/{}] Converting {}", i + 1, total, worksheet);
+                }
+                let name = format!("worksheet {}", worksheet);
+                let base = options.sheet_parse(&worksheet);
+                let parsed = range.and_then(|range| with_prompted_ftp(
+                    &name, &base, options, |parse| parse_range(&range, parse)));
+                convert(file, Some(&worksheet), parsed, options)
+            })
+            .collect())
+    }
+}
+
+/// Converts all files given in `options`. A file which can't be opened or a 
+/// worksheet which can't be parsed doesn't stop the others, but makes the run 
+/// fail in the end. The outcome of every worksheet is added to `report`.
+fn run(options: &Options, report: &mut Report) -> Result<(), Box<dyn Error>> {
+    if let Some(old) = &options.compare {
+        return compare(old, &options.files[0], options);
+    }
+    let mut workouts = Vec::new();
+    let mut failed = 0;
+    let mut failed_files = Vec::new();
+    for file in &options.files {
+        match convert_file(file, options) {
+            Ok(converted) => for conversion in converted {
+                match conversion.workout {
+                    Some(workout) => workouts.push(workout),
+                    None => failed += 1,
+                }
+                report.sheets.push(conversion.report);
+            },
+            Err(e) => {
+                error!("{}", e);
+                report.sheets.push(SheetReport::failed(file, None, &e));
+                failed_files.push(file.as_str());
+            },
+        }
+    }
+
+    // e.g. an empty workbook, a skip list matching every worksheet or only 
+    // worksheets which can't be parsed
+    let all_failed = !options.dry_run && workouts.is_empty() && failed > 0 
+        && UNWRITTEN.load(Ordering::Relaxed) == 0;
+    if !options.list && !options.validate && failed_files.is_empty() 
+        && (CANDIDATES.load(Ordering::Relaxed) == 0 || all_failed) {
+        return Err("no convertible worksheets found".into());
+    }
+
+    // skipped and failed worksheets aren't part of the block, neither are 
+    // workouts whose file couldn't be written
+    let total_tss: f64 = workouts.iter().map(|workout| workout.tss).sum();
+    report.total_tss = total_tss;
+    let total_minutes: f64 = workouts.iter().map(Workout::duration).sum();
+    let count = workouts.len();
+
+    if let Some(file_name) = &options.merge {
+        // a merged workout with missing parts would be misleading
+        if failed > 0 || !failed_files.is_empty() {
+            return Err(format!("not merging, {} worksheet(s) and {} file(s) \
+                               failed", failed, failed_files.len()).into());
+        }
+        let (merged, destination) = merge(&workouts, file_name, options)?;
+        report.merged = destination;
+        workouts = vec![merged];
+    }
+    if count > 0 {
+        info!(target: OUTPUT, "{}", total_summary(total_tss, count, 
+                                                  total_minutes, 
+                                                  options.summary_format, 
+                                                  options.tss_decimals));
+    }
+
+    // summarize all workouts in the same order
+    if !options.dry_run && !options.list && !options.validate 
+        && !options.stdout 
+        && failed_files.len() < options.files.len() {
+        if let Err(e) = write_overview_file(&workouts, &options.outdir, 
+                                            &options.write) {
+            error!("Couldn't write overview: {}", e);
+        }
+    }
+
+    let floored = FLOORED.load(Ordering::Relaxed);
+    if let (Some(floor), true) = (options.watt_floor, floored > 0) {
+        warn!("Raised {} data point(s) below {} W to the watt floor", 
+              floored, floor);
+    }
+
+    if !failed_files.is_empty() {
+        return Err(format!("couldn't convert {}", failed_files.join(", "))
+                   .into());
+    }
+    let unreadable = UNREADABLE.load(Ordering::Relaxed);
+    if unreadable > 0 {
+        return Err(format!("{} worksheet(s) couldn't be read", unreadable)
+                   .into());
+    }
+    let unwritten = UNWRITTEN.load(Ordering::Relaxed);
+    if unwritten > 0 {
+        return Err(format!("{} file(s) couldn't be written", unwritten)
+                   .into());
+    }
+    let invalid = INVALID.load(Ordering::Relaxed);
+    if invalid > 0 {
+        return Err(format!("{} worksheet(s) failed validation", invalid)
+                   .into());
+    }
+    let mismatches = MISMATCHES.load(Ordering::Relaxed);
+    if mismatches > 0 {
+        return Err(format!("{} file(s) failed verification", mismatches)
+                   .into());
+    }
+    // unreadable worksheets and unwritten workouts are part of `failed`, so 
+    // this comes last
+    if failed > 0 {
+        return Err(format!("{} worksheet(s) failed to parse", failed).into());
+    }
+    Ok(())
 }
 
 fn main() {
-    // Check argument
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        panic!("Usage: {} <file>", args[0]);
-    }
-
-    // open workbook and get worksheets
-    let mut workbook = Excel::open(args[1].to_owned())
-        .expect("Couldn't open Excel file");
-    let mut worksheets = workbook.sheet_names()
-        .expect("Couldn't get worksheets");
-    worksheets.sort();
-
-    // loop over worksheets, parse content and write `erg` files
-    for worksheet in worksheets {
-        if worksheet == "Overview" { continue; }
-        let workout = parse_workout(&mut workbook, &worksheet);
-        println!("{}", workout);
-        write_erg_file(workout);
+    // Check arguments
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}\n{}", e, USAGE);
+            process::exit(2);
+        },
+    };
+
+    log::set_logger(&LOGGER).expect("logger is only set once");
+    LOGGER.stderr_only.store(options.stdout, Ordering::Relaxed);
+    log::set_max_level(match options.verbosity {
+        _ if options.quiet => LevelFilter::Error,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    });
+
+    let mut report = Report::default();
+    let result = run(&options, &mut report);
+    if let Some(path) = &options.report {
+        report.success = result.is_ok();
+        report.error = result.as_ref().err().map(|e| e.to_string());
+        if let Err(e) = write_report_file(&report, path) {
+            eprintln!("Error: couldn't write report {}: {}", path.display(), 
+                      e);
+            process::exit(1);
+        }
+    }
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
     }
 }
@@ -0,0 +1,327 @@
+//! Converts the fixture workbook with the binary and compares the written
+//! files with the expected output.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"),
+                              "/tests/fixtures/workout.xlsx");
+
+const GOLDEN_ERG: &str = "[COURSE HEADER]
+VERSION = 2
+UNITS = ENGLISH
+DESCRIPTION = Fixture workout
+FILE NAME = fixture.erg
+FTP = 250
+MINUTES WATTS
+[END COURSE HEADER]
+[COURSE DATA]
+0.00\t125
+10.00\t125
+10.00\t225
+20.00\t225
+20.00\t125
+25.00\t125
+[END COURSE DATA]
+";
+
+/// Empty output directory which is removed again when dropped
+struct OutputDir(PathBuf);
+
+impl OutputDir {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir()
+            .join(format!("xlsx2erg-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        OutputDir(path)
+    }
+}
+
+impl Drop for OutputDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn convert(outdir: &OutputDir, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+        .arg("-q")
+        .arg("--outdir")
+        .arg(&outdir.0)
+        .args(args)
+        .arg(FIXTURE)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn golden_erg() {
+    let outdir = OutputDir::new("golden");
+    convert(&outdir, &[]);
+    assert_eq!(fs::read_to_string(outdir.0.join("fixture.erg")).unwrap(),
+               GOLDEN_ERG);
+
+    // the Overview worksheet is skipped
+    let mut written: Vec<_> = fs::read_dir(&outdir.0).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    written.sort();
+    assert_eq!(written, ["fixture.erg", "summary.txt"]);
+}
+
+#[test]
+fn verified_erg() {
+    let outdir = OutputDir::new("verified");
+    convert(&outdir, &["--verify", "--ramp-resolution", "30"]);
+    assert!(outdir.0.join("fixture.erg").exists());
+}
+
+#[test]
+fn no_clobber() {
+    let outdir = OutputDir::new("no-clobber");
+    convert(&outdir, &["--no-clobber"]);
+    let report = outdir.0.join("report.json");
+    let output = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+        .arg("--no-clobber")
+        .arg("--report")
+        .arg(&report)
+        .arg("--outdir")
+        .arg(&outdir.0)
+        .arg(FIXTURE)
+        .output()
+        .unwrap();
+    // the refused file isn't part of the total
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("TOTAL"));
+    assert!(String::from_utf8(output.stderr).unwrap()
+            .contains("1 file(s) couldn't be written"));
+    let report: serde_json::Value = 
+        serde_json::from_str(&fs::read_to_string(report).unwrap()).unwrap();
+    assert_eq!(report["success"], false);
+    assert_eq!(report["total_tss"], 0.0);
+}
+
+#[test]
+fn json_report() {
+    let outdir = OutputDir::new("report");
+    let report = outdir.0.join("report.json");
+    convert(&outdir, &["--report", report.to_str().unwrap()]);
+    let report: serde_json::Value = 
+        serde_json::from_str(&fs::read_to_string(report).unwrap()).unwrap();
+    assert_eq!(report["success"], true);
+    assert_eq!(report["sheets"].as_array().unwrap().len(), 1);
+    assert_eq!(report["sheets"][0]["sheet"], "endurance");
+    assert!(report["sheets"][0]["output"].as_str().unwrap()
+            .ends_with("fixture.erg"));
+}
+
+#[test]
+fn ftp_precedence() {
+    let outdir = OutputDir::new("ftp");
+    let ftp_file = outdir.0.join("ftp.toml");
+    fs::create_dir_all(&outdir.0).unwrap();
+    fs::write(&ftp_file, "# shared by all plans\nftp = 200\n").unwrap();
+    let ftp_file = ftp_file.to_str().unwrap();
+    let ftp = |args: &[&str]| {
+        convert(&outdir, args);
+        let erg = fs::read_to_string(outdir.0.join("fixture.erg")).unwrap();
+        erg.lines().find(|line| line.starts_with("FTP = ")).unwrap()
+            .to_string()
+    };
+
+    assert_eq!(ftp(&[]), "FTP = 250");
+    assert_eq!(ftp(&["--ftp-file", ftp_file]), "FTP = 200");
+    assert_eq!(ftp(&["--ftp-file", ftp_file, "--ftp", "300"]), "FTP = 300");
+
+    let ftp_map = outdir.0.join("ftp-map.toml");
+    fs::write(&ftp_map, "endur* = 220\n").unwrap();
+    let ftp_map = ftp_map.to_str().unwrap();
+    assert_eq!(ftp(&["--ftp-map", ftp_map]), "FTP = 220");
+    assert_eq!(ftp(&["--ftp-map", ftp_map, "--ftp-file", ftp_file]), 
+               "FTP = 220");
+    assert_eq!(ftp(&["--ftp-map", ftp_map, "--ftp", "300"]), "FTP = 300");
+    // worksheets missing in the map keep their FTP
+    fs::write(ftp_map, "threshold = 220\n").unwrap();
+    assert_eq!(ftp(&["--ftp-map", ftp_map]), "FTP = 250");
+    assert_eq!(ftp(&["--ftp-map", ftp_map, "--ftp-file", ftp_file]), 
+               "FTP = 200");
+}
+
+#[test]
+fn forced_ftp() {
+    let outdir = OutputDir::new("force-ftp");
+    fs::create_dir_all(&outdir.0).unwrap();
+    let (a, b) = (outdir.0.join("a.csv"), outdir.0.join("b.csv"));
+    fs::write(&a, "FTP=250\nNAME=a.erg\n0,0.5\n10,1.0\n").unwrap();
+    fs::write(&b, "FTP=300\nNAME=b.erg\n0,0.8\n10,0.8\n").unwrap();
+    let merge = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+            .arg("--outdir")
+            .arg(&outdir.0)
+            .args(["--merge", "m.erg"])
+            .args(args)
+            .arg(&a)
+            .arg(&b)
+            .output()
+            .unwrap();
+        (output.status.code(), String::from_utf8(output.stdout).unwrap(), 
+         String::from_utf8(output.stderr).unwrap())
+    };
+
+    // the mismatching FTP is named
+    let (code, _, stderr) = merge(&[]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("FTP differs from the 250 of the first workout: \
+                             b.erg (300)"), "{}", stderr);
+    assert!(!outdir.0.join("m.erg").exists());
+
+    // the forced FTP replaces both FTP cells, keeping the intensities
+    let (code, _, stderr) = merge(&["--force-ftp", "200"]);
+    assert_eq!(code, Some(0));
+    assert!(stderr.contains("changed the FTP from 250 to 200 W"), "{}", stderr);
+    assert!(stderr.contains("changed the FTP from 300 to 200 W"), "{}", stderr);
+    let erg = fs::read_to_string(outdir.0.join("m.erg")).unwrap();
+    assert!(erg.contains("FTP = 200\n"), "{}", erg);
+    // the boundary is marked with the file names, as there's no description
+    assert!(erg.contains("[COURSE DATA]\n; a.erg\n0.00\t100\n10.00\t200\n\
+                          ; b.erg\n10.00\t160\n20.00\t160\n"), "{}", erg);
+
+    // a matching FTP is kept without a warning
+    let (code, _, stderr) = merge(&["--force-ftp", "250"]);
+    assert_eq!(code, Some(0));
+    assert!(!stderr.contains("from 250"), "{}", stderr);
+    assert!(stderr.contains("changed the FTP from 300 to 250 W"), "{}", stderr);
+
+    // the interpolated points don't change the TSS
+    let summary = |args: &[&str]| {
+        let args = [&["--dry-run", "--force-ftp", "200"], args].concat();
+        merge(&args).1.lines()
+            .find(|line| line.starts_with("m.erg"))
+            .unwrap()
+            .to_string()
+    };
+    let plain = summary(&[]);
+    assert!(plain.contains("TSS:    20 "), "{}", plain);
+    assert_eq!(summary(&["--ramp-resolution", "60"]), plain);
+    assert_eq!(summary(&["--sample-interval", "60"]), plain);
+}
+
+#[test]
+fn overview_only() {
+    let outdir = OutputDir::new("overview");
+    let fails = |args: &[&str], file: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+            .arg("--outdir")
+            .arg(&outdir.0)
+            .args(args)
+            .arg(file)
+            .output()
+            .unwrap();
+        output.status.code() == Some(1) 
+            && String::from_utf8_lossy(&output.stderr)
+                .contains("no convertible worksheets found")
+    };
+    assert!(fails(&[], concat!(env!("CARGO_MANIFEST_DIR"), 
+                               "/tests/fixtures/overview.xlsx")));
+    assert!(!outdir.0.join("summary.txt").exists());
+    // the overview isn't a workout either
+    assert!(fails(&["--skip-sheet", "none"], 
+                  concat!(env!("CARGO_MANIFEST_DIR"), 
+                          "/tests/fixtures/overview.xlsx")));
+    assert!(!outdir.0.join("summary.txt").exists());
+    // a skip list matching every worksheet
+    assert!(fails(&["--skip-sheet", "Overview", "--skip-sheet", "endurance"], 
+                  FIXTURE));
+    assert!(!fails(&["--skip-sheet", "Overview"], FIXTURE));
+}
+
+#[test]
+fn validate() {
+    let outdir = OutputDir::new("validate");
+    let validate = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+            .arg("--validate")
+            .arg("--outdir")
+            .arg(&outdir.0)
+            .args(args)
+            .arg(FIXTURE)
+            .output()
+            .unwrap();
+        (output.status.code(), String::from_utf8(output.stdout).unwrap())
+    };
+    assert_eq!(validate(&[]), 
+               (Some(0), "SKIP     Overview\nPASS     endurance\n".to_string()));
+    assert!(!outdir.0.exists());
+
+    // the skip list ignores the case and surrounding whitespace
+    assert_eq!(validate(&["--skip-sheet", " OVERVIEW "]).1, 
+               "SKIP     Overview\nPASS     endurance\n");
+
+    // the overview isn't a workout
+    let (code, stdout) = validate(&["--skip-sheet", "none"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.starts_with("FAIL     Overview\n         error: "));
+}
+
+#[test]
+fn quiet() {
+    let outdir = OutputDir::new("quiet");
+    let output = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+        .arg("--quiet")
+        .arg("--outdir")
+        .arg(&outdir.0)
+        .arg(FIXTURE)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+    assert!(outdir.0.join("fixture.erg").exists());
+}
+
+#[test]
+fn corrupt_workbook() {
+    let outdir = OutputDir::new("corrupt");
+    fs::create_dir_all(&outdir.0).unwrap();
+    let file = outdir.0.join("corrupt.xlsx");
+    fs::write(&file, "not a workbook").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+        .arg("--outdir")
+        .arg(&outdir.0)
+        .arg(&file)
+        .output()
+        .unwrap();
+    // reported with the file and the reason instead of a panic
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let message = format!("Error: couldn't open {}: ", file.display());
+    assert!(stderr.starts_with(&message), "{}", stderr);
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn compare() {
+    let compare = |old: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_xlsx2erg"))
+            .arg("--compare")
+            .arg(old)
+            .arg(FIXTURE)
+            .output()
+            .unwrap();
+        (output.status.code(), String::from_utf8(output.stdout).unwrap())
+    };
+    let (code, stdout) = compare(FIXTURE);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout.lines().nth(1).unwrap().split_whitespace()
+                   .collect::<Vec<_>>(), 
+               ["endurance", "20", "20", "0"]);
+
+    // the previous version had no workouts yet
+    let (code, stdout) = compare(concat!(env!("CARGO_MANIFEST_DIR"), 
+                                         "/tests/fixtures/overview.xlsx"));
+    assert_eq!(code, Some(1));
+    assert!(stdout.lines().nth(1).unwrap().ends_with(" only in workout.xlsx"));
+}